@@ -1,16 +1,15 @@
 pub mod schema;
+pub mod units;
 
 pub use schema::*;
+pub use units::{Quantity, Unit};
 
 use crate::error::ConfigError;
 use std::path::{Path, PathBuf};
 
 impl AgentConfig {
     pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
-        let config_path = path
-            .map(PathBuf::from)
-            .or_else(Self::default_config_path)
-            .ok_or(ConfigError::NotFound)?;
+        let config_path = Self::resolve_path(path).ok_or(ConfigError::NotFound)?;
 
         if !config_path.exists() {
             return Err(ConfigError::NotFound);
@@ -22,13 +21,64 @@ impl AgentConfig {
         // Expand environment variables in the content
         let expanded = expand_env_vars(&content);
 
-        let config: AgentConfig =
+        let mut config: AgentConfig =
             toml::from_str(&expanded).map_err(|e| ConfigError::ParseError(e.to_string()))?;
 
         config.validate()?;
+        config.normalize();
         Ok(config)
     }
 
+    /// The file `load` reads: `path` when given, the platform default otherwise.
+    pub fn resolve_path(path: Option<&Path>) -> Option<PathBuf> {
+        path.map(PathBuf::from).or_else(Self::default_config_path)
+    }
+
+    /// POI API key readings from `source` are sent with.
+    pub fn api_key_for<'a>(&'a self, source: &'a SourceConfig) -> &'a str {
+        source.api_key.as_deref().unwrap_or(&self.poi.api_key)
+    }
+
+    pub fn polling_interval_for(&self, source: &SourceConfig) -> u64 {
+        source
+            .polling_interval_secs
+            .unwrap_or(self.agent.polling_interval_secs)
+    }
+
+    /// Fold the single `[source]` and `[supabase]` tables into `sources` and `sinks`
+    /// so the rest of the agent only deals with lists.
+    fn normalize(&mut self) {
+        if let Some(mut source) = self.source.take() {
+            if source.name.is_empty() {
+                source.name = "default".to_string();
+            }
+            self.sources.insert(0, source);
+        }
+
+        // Sources converting their readings report them in the POI's unit unless they name their own
+        if let Some(poi_unit) = self.poi.unit {
+            for source in &mut self.sources {
+                if source.input_unit.is_some() {
+                    if let Some(unit) = source.unit_mut().filter(|u| u.is_empty()) {
+                        *unit = poi_unit.to_string();
+                    }
+                }
+            }
+        }
+
+        if let Some(supabase) = self.supabase.take() {
+            self.sinks.insert(
+                0,
+                SinkConfig {
+                    name: "supabase".to_string(),
+                    sink_type: SinkType::Supabase,
+                    supabase: Some(supabase),
+                    http: None,
+                },
+            );
+        }
+    }
+
     pub fn default_config_path() -> Option<PathBuf> {
         #[cfg(target_os = "macos")]
         {
@@ -63,6 +113,23 @@ impl AgentConfig {
         }
     }
 
+    pub fn default_data_dir() -> Option<PathBuf> {
+        #[cfg(target_os = "macos")]
+        {
+            dirs::data_dir().map(|p| p.join("agentquelia"))
+        }
+
+        #[cfg(target_os = "windows")]
+        {
+            dirs::data_local_dir().map(|p| p.join("agentquelia\\data"))
+        }
+
+        #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+        {
+            dirs::data_local_dir().map(|p| p.join("agentquelia/data"))
+        }
+    }
+
     fn validate(&self) -> Result<(), ConfigError> {
         // Validate instance_id
         if self.agent.instance_id.is_empty() {
@@ -78,86 +145,449 @@ impl AgentConfig {
             ));
         }
 
-        // Validate POI API key
-        if self.poi.api_key.is_empty() {
+        // Validate destinations
+        if self.supabase.is_none() && self.sinks.is_empty() {
             return Err(ConfigError::ValidationError(
-                "poi.api_key cannot be empty".to_string(),
+                "at least one destination must be configured in [supabase] or [[sinks]]"
+                    .to_string(),
             ));
         }
 
-        // Validate Supabase settings
-        if self.supabase.url.is_empty() {
+        let mut sink_names = std::collections::HashSet::new();
+        if let Some(supabase) = &self.supabase {
+            validate_supabase(supabase, "supabase")?;
+            sink_names.insert("supabase");
+        }
+
+        for (i, sink) in self.sinks.iter().enumerate() {
+            if sink.name.is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "sinks[{}].name cannot be empty",
+                    i
+                )));
+            }
+            // The name is also the outbox file name
+            if !sink
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(ConfigError::ValidationError(format!(
+                    "sinks.{}.name may only contain letters, digits, '-' and '_'",
+                    sink.name
+                )));
+            }
+            if !sink_names.insert(sink.name.as_str()) {
+                return Err(ConfigError::ValidationError(format!(
+                    "duplicate sink name '{}'",
+                    sink.name
+                )));
+            }
+            validate_sink(sink, &format!("sinks.{}", sink.name))?;
+        }
+
+        // Validate outbox limits
+        if self.outbox.max_entries == 0 {
             return Err(ConfigError::ValidationError(
-                "supabase.url cannot be empty".to_string(),
+                "outbox.max_entries must be greater than 0".to_string(),
             ));
         }
 
-        if self.supabase.anon_key.is_empty() {
+        if self.health.enabled && self.health.bind.parse::<std::net::SocketAddr>().is_err() {
+            return Err(ConfigError::ValidationError(format!(
+                "health.bind '{}' is not a valid address (expected host:port)",
+                self.health.bind
+            )));
+        }
+
+        // Validate source configuration
+        if self.source.is_none() && self.sources.is_empty() {
             return Err(ConfigError::ValidationError(
-                "supabase.anon_key cannot be empty".to_string(),
+                "at least one source must be configured in [source] or [[sources]]".to_string(),
             ));
         }
 
-        // Validate source configuration
-        match self.source.source_type {
+        let mut names = std::collections::HashSet::new();
+        if let Some(source) = &self.source {
+            self.validate_source(source, "source")?;
+            names.insert(if source.name.is_empty() {
+                "default"
+            } else {
+                source.name.as_str()
+            });
+        }
+
+        for (i, source) in self.sources.iter().enumerate() {
+            if source.name.is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "sources[{}].name cannot be empty",
+                    i
+                )));
+            }
+            if !names.insert(source.name.as_str()) {
+                return Err(ConfigError::ValidationError(format!(
+                    "duplicate source name '{}'",
+                    source.name
+                )));
+            }
+            self.validate_source(source, &format!("sources.{}", source.name))?;
+        }
+
+        self.validate_fallbacks()?;
+
+        Ok(())
+    }
+
+    /// Fallbacks must name other sources that report in the same unit, and each
+    /// source can only back up one other.
+    fn validate_fallbacks(&self) -> Result<(), ConfigError> {
+        let all: Vec<&SourceConfig> = self.source.iter().chain(&self.sources).collect();
+        let name = |source: &SourceConfig| match source.name.as_str() {
+            "" => "default".to_string(),
+            name => name.to_string(),
+        };
+        let unit = |source: &SourceConfig| match source.unit().filter(|u| !u.is_empty()) {
+            Some(unit) => unit.to_string(),
+            None => self.poi.unit.map(|u| u.to_string()).unwrap_or_default(),
+        };
+
+        let mut used = std::collections::HashSet::new();
+        for source in &all {
+            let primary = name(source);
+            for fallback_name in &source.fallbacks {
+                let invalid = |reason: &str| {
+                    ConfigError::ValidationError(format!(
+                        "sources.{}.fallbacks: '{}' {}",
+                        primary, fallback_name, reason
+                    ))
+                };
+                if *fallback_name == primary {
+                    return Err(invalid("is the source itself"));
+                }
+                let fallback = all
+                    .iter()
+                    .find(|s| name(s) == *fallback_name)
+                    .ok_or_else(|| invalid("is not a configured source"))?;
+                if !fallback.fallbacks.is_empty() {
+                    return Err(invalid("has fallbacks of its own"));
+                }
+                if !used.insert(fallback_name.as_str()) {
+                    return Err(invalid("is already a fallback for another source"));
+                }
+                if !unit(fallback).eq_ignore_ascii_case(&unit(source)) {
+                    return Err(invalid(&format!(
+                        "reports in {} rather than {}",
+                        unit(fallback),
+                        unit(source)
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn validate_source(&self, source: &SourceConfig, prefix: &str) -> Result<(), ConfigError> {
+        if self.api_key_for(source).is_empty() {
+            return Err(ConfigError::ValidationError(format!(
+                "{}.api_key or poi.api_key must be set",
+                prefix
+            )));
+        }
+
+        if self.polling_interval_for(source) == 0 {
+            return Err(ConfigError::ValidationError(format!(
+                "{}.polling_interval_secs must be greater than 0",
+                prefix
+            )));
+        }
+
+        match source.source_type {
             SourceType::Csv => {
-                if self.source.csv.is_none() {
-                    return Err(ConfigError::ValidationError(
-                        "source.csv is required when type is 'csv'".to_string(),
-                    ));
+                let csv = source.csv.as_ref().ok_or_else(|| {
+                    ConfigError::ValidationError(format!(
+                        "{}.csv is required when type is 'csv'",
+                        prefix
+                    ))
+                })?;
+                match (csv.value_field.is_empty(), &csv.expression) {
+                    (true, None) => {
+                        return Err(ConfigError::ValidationError(format!(
+                            "{}.csv.value_field cannot be empty",
+                            prefix
+                        )))
+                    }
+                    (false, Some(_)) => {
+                        return Err(ConfigError::ValidationError(format!(
+                            "{}.csv: set either value_field or expression, not both",
+                            prefix
+                        )))
+                    }
+                    _ => {}
                 }
-                let csv = self.source.csv.as_ref().unwrap();
-                if csv.value_field.is_empty() {
-                    return Err(ConfigError::ValidationError(
-                        "source.csv.value_field cannot be empty".to_string(),
-                    ));
+                if csv.unit.is_empty() && source.input_unit.is_none() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.csv.unit cannot be empty",
+                        prefix
+                    )));
                 }
-                if csv.unit.is_empty() {
-                    return Err(ConfigError::ValidationError(
-                        "source.csv.unit cannot be empty".to_string(),
-                    ));
+                if csv.mode != CsvReadMode::Full && !csv.read_last_row {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.csv.mode \"tail\" and \"incremental\" require read_last_row = true",
+                        prefix
+                    )));
+                }
+                if csv.max_age_secs == Some(0) {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.csv.max_age_secs must be greater than 0",
+                        prefix
+                    )));
                 }
             }
             SourceType::Json => {
-                if self.source.json.is_none() {
-                    return Err(ConfigError::ValidationError(
-                        "source.json is required when type is 'json'".to_string(),
-                    ));
+                let json = source.json.as_ref().ok_or_else(|| {
+                    ConfigError::ValidationError(format!(
+                        "{}.json is required when type is 'json'",
+                        prefix
+                    ))
+                })?;
+                match (json.json_path.is_empty(), &json.expression) {
+                    (true, None) => {
+                        return Err(ConfigError::ValidationError(format!(
+                            "{}.json.json_path cannot be empty",
+                            prefix
+                        )))
+                    }
+                    (false, Some(_)) => {
+                        return Err(ConfigError::ValidationError(format!(
+                            "{}.json: set either json_path or expression, not both",
+                            prefix
+                        )))
+                    }
+                    _ => {}
                 }
-                let json = self.source.json.as_ref().unwrap();
-                if json.json_path.is_empty() {
-                    return Err(ConfigError::ValidationError(
-                        "source.json.json_path cannot be empty".to_string(),
-                    ));
+                if json.unit.is_empty() && source.input_unit.is_none() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.json.unit cannot be empty",
+                        prefix
+                    )));
                 }
-                if json.unit.is_empty() {
-                    return Err(ConfigError::ValidationError(
-                        "source.json.unit cannot be empty".to_string(),
-                    ));
+                if json.max_age_secs == Some(0) {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.json.max_age_secs must be greater than 0",
+                        prefix
+                    )));
                 }
             }
             SourceType::Http => {
-                if self.source.http.is_none() {
-                    return Err(ConfigError::ValidationError(
-                        "source.http is required when type is 'http'".to_string(),
-                    ));
-                }
-                let http = self.source.http.as_ref().unwrap();
+                let http = source.http.as_ref().ok_or_else(|| {
+                    ConfigError::ValidationError(format!(
+                        "{}.http is required when type is 'http'",
+                        prefix
+                    ))
+                })?;
                 if http.url.is_empty() {
-                    return Err(ConfigError::ValidationError(
-                        "source.http.url cannot be empty".to_string(),
-                    ));
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.http.url cannot be empty",
+                        prefix
+                    )));
                 }
-                if http.json_path.is_empty() {
-                    return Err(ConfigError::ValidationError(
-                        "source.http.json_path cannot be empty".to_string(),
-                    ));
+                // The setting that locates the value in this response format, and whether it is set
+                let value_setting = match http.format {
+                    HttpResponseFormat::Json => Some(("json_path", !http.json_path.is_empty())),
+                    HttpResponseFormat::Csv => Some(("value_field", !http.value_field.is_empty())),
+                    HttpResponseFormat::Xml => Some(("xml_path", http.xml_path.is_some())),
+                    HttpResponseFormat::Regex | HttpResponseFormat::Number => None,
+                };
+                match (value_setting, &http.expression) {
+                    (Some((setting, false)), None) => {
+                        return Err(ConfigError::ValidationError(format!(
+                            "{}.http.{} cannot be empty",
+                            prefix, setting
+                        )))
+                    }
+                    (Some((setting, true)), Some(_)) => {
+                        return Err(ConfigError::ValidationError(format!(
+                            "{}.http: set either {} or expression, not both",
+                            prefix, setting
+                        )))
+                    }
+                    (None, Some(_)) => {
+                        return Err(ConfigError::ValidationError(format!(
+                            "{}.http.expression is not supported for {} responses",
+                            prefix,
+                            format!("{:?}", http.format).to_lowercase()
+                        )))
+                    }
+                    _ => {}
                 }
-                if http.unit.is_empty() {
-                    return Err(ConfigError::ValidationError(
-                        "source.http.unit cannot be empty".to_string(),
-                    ));
+                if http.format == HttpResponseFormat::Regex && http.pattern.is_none() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.http.pattern is required for regex responses",
+                        prefix
+                    )));
                 }
+                if http.unit.is_empty() && source.input_unit.is_none() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.http.unit cannot be empty",
+                        prefix
+                    )));
+                }
+                if let Some(auth) = &http.auth {
+                    validate_auth(auth, &format!("{}.http.auth", prefix))?;
+                }
+                if http.max_age_secs == Some(0) {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.http.max_age_secs must be greater than 0",
+                        prefix
+                    )));
+                }
+            }
+            SourceType::Modbus => {
+                let modbus = source.modbus.as_ref().ok_or_else(|| {
+                    ConfigError::ValidationError(format!(
+                        "{}.modbus is required when type is 'modbus'",
+                        prefix
+                    ))
+                })?;
+                if modbus.host.is_empty() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.modbus.host cannot be empty",
+                        prefix
+                    )));
+                }
+                if modbus.unit.is_empty() && source.input_unit.is_none() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.modbus.unit cannot be empty",
+                        prefix
+                    )));
+                }
+            }
+            SourceType::Mqtt => {
+                let mqtt = source.mqtt.as_ref().ok_or_else(|| {
+                    ConfigError::ValidationError(format!(
+                        "{}.mqtt is required when type is 'mqtt'",
+                        prefix
+                    ))
+                })?;
+                if mqtt.host.is_empty() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.mqtt.host cannot be empty",
+                        prefix
+                    )));
+                }
+                if mqtt.topic.is_empty() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.mqtt.topic cannot be empty",
+                        prefix
+                    )));
+                }
+                if mqtt.unit.is_empty() && source.input_unit.is_none() {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.mqtt.unit cannot be empty",
+                        prefix
+                    )));
+                }
+                if mqtt.qos > 2 {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.mqtt.qos must be 0, 1 or 2",
+                        prefix
+                    )));
+                }
+                if mqtt.stale_after_intervals == 0 {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.mqtt.stale_after_intervals must be greater than 0",
+                        prefix
+                    )));
+                }
+            }
+        }
+
+        if let Some(counter) = &source.counter {
+            if source.input_unit.map(Unit::quantity) != Some(Quantity::Energy) {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}.counter requires an energy input_unit (Wh, kWh or MWh)",
+                    prefix
+                )));
+            }
+            if counter.rollover.is_some_and(|r| r <= 0.0) {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}.counter.rollover must be positive",
+                    prefix
+                )));
+            }
+        }
+
+        if let Some(filter) = &source.filter {
+            if let (Some(min), Some(max)) = (filter.min, filter.max) {
+                if min > max {
+                    return Err(ConfigError::ValidationError(format!(
+                        "{}.filter.min cannot be greater than max",
+                        prefix
+                    )));
+                }
+            }
+            if filter.max_rate_per_sec.is_some_and(|rate| rate <= 0.0) {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}.filter.max_rate_per_sec must be positive",
+                    prefix
+                )));
+            }
+        }
+
+        if source.retry.as_ref().is_some_and(|r| r.max_attempts == 0) {
+            return Err(ConfigError::ValidationError(format!(
+                "{}.retry.max_attempts must be greater than 0",
+                prefix
+            )));
+        }
+
+        if let Some(aggregate) = &source.aggregate {
+            if aggregate.window_secs < self.polling_interval_for(source) {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}.aggregate.window_secs must be at least the polling interval",
+                    prefix
+                )));
+            }
+        }
+
+        if let Some(input_unit) = source.input_unit {
+            // The conversion already scales the value, a leftover multiplier would apply twice
+            if source.multiplier().is_some_and(|m| m != 1.0) {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}: input_unit already converts the value, remove the multiplier",
+                    prefix
+                )));
+            }
+
+            // A counter reports power in the unit matching its register
+            let input_unit = match source.counter {
+                Some(_) => input_unit.power(),
+                None => input_unit,
+            };
+            let target = match source.unit().filter(|u| !u.is_empty()) {
+                Some(unit) => unit.parse::<Unit>().map_err(|e| {
+                    ConfigError::ValidationError(format!(
+                        "{}: input_unit needs a convertible unit, {}",
+                        prefix, e
+                    ))
+                })?,
+                None => self.poi.unit.ok_or_else(|| {
+                    ConfigError::ValidationError(format!(
+                        "{}: input_unit requires the source's unit or poi.unit",
+                        prefix
+                    ))
+                })?,
+            };
+            if input_unit.factor_to(target).is_none() {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}: cannot convert {} ({}) to {} ({})",
+                    prefix,
+                    input_unit,
+                    input_unit.quantity(),
+                    target,
+                    target.quantity()
+                )));
             }
         }
 
@@ -165,6 +595,114 @@ impl AgentConfig {
     }
 }
 
+impl SourceConfig {
+    /// The unit readings are reported in, from the section matching `source_type`.
+    pub fn unit(&self) -> Option<&str> {
+        match self.source_type {
+            SourceType::Csv => self.csv.as_ref().map(|c| c.unit.as_str()),
+            SourceType::Json => self.json.as_ref().map(|c| c.unit.as_str()),
+            SourceType::Http => self.http.as_ref().map(|c| c.unit.as_str()),
+            SourceType::Modbus => self.modbus.as_ref().map(|c| c.unit.as_str()),
+            SourceType::Mqtt => self.mqtt.as_ref().map(|c| c.unit.as_str()),
+        }
+    }
+
+    /// The factor readings are scaled by, from the section matching `source_type`.
+    pub fn multiplier(&self) -> Option<f64> {
+        match self.source_type {
+            SourceType::Csv => self.csv.as_ref().map(|c| c.multiplier),
+            SourceType::Json => self.json.as_ref().map(|c| c.multiplier),
+            SourceType::Http => self.http.as_ref().map(|c| c.multiplier),
+            SourceType::Modbus => self.modbus.as_ref().map(|c| c.multiplier),
+            SourceType::Mqtt => self.mqtt.as_ref().map(|c| c.multiplier),
+        }
+    }
+
+    fn unit_mut(&mut self) -> Option<&mut String> {
+        match self.source_type {
+            SourceType::Csv => self.csv.as_mut().map(|c| &mut c.unit),
+            SourceType::Json => self.json.as_mut().map(|c| &mut c.unit),
+            SourceType::Http => self.http.as_mut().map(|c| &mut c.unit),
+            SourceType::Modbus => self.modbus.as_mut().map(|c| &mut c.unit),
+            SourceType::Mqtt => self.mqtt.as_mut().map(|c| &mut c.unit),
+        }
+    }
+}
+
+fn validate_sink(sink: &SinkConfig, prefix: &str) -> Result<(), ConfigError> {
+    match sink.sink_type {
+        SinkType::Supabase => {
+            let supabase = sink.supabase.as_ref().ok_or_else(|| {
+                ConfigError::ValidationError(format!(
+                    "{}.supabase is required when type is 'supabase'",
+                    prefix
+                ))
+            })?;
+            validate_supabase(supabase, &format!("{}.supabase", prefix))
+        }
+        SinkType::Http => {
+            let http = sink.http.as_ref().ok_or_else(|| {
+                ConfigError::ValidationError(format!(
+                    "{}.http is required when type is 'http'",
+                    prefix
+                ))
+            })?;
+            if http.url.is_empty() {
+                return Err(ConfigError::ValidationError(format!(
+                    "{}.http.url cannot be empty",
+                    prefix
+                )));
+            }
+            match &http.auth {
+                Some(auth) => validate_auth(auth, &format!("{}.http.auth", prefix)),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+fn validate_auth(auth: &HttpAuthConfig, prefix: &str) -> Result<(), ConfigError> {
+    if let HttpAuthConfig::OAuth2 {
+        token_url,
+        client_id,
+        ..
+    } = auth
+    {
+        if token_url.is_empty() || client_id.is_empty() {
+            return Err(ConfigError::ValidationError(format!(
+                "{}: token_url and client_id are required for oauth2",
+                prefix
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_supabase(supabase: &SupabaseSettings, prefix: &str) -> Result<(), ConfigError> {
+    if supabase.url.is_empty() {
+        return Err(ConfigError::ValidationError(format!(
+            "{}.url cannot be empty",
+            prefix
+        )));
+    }
+
+    if supabase.anon_key.is_empty() {
+        return Err(ConfigError::ValidationError(format!(
+            "{}.anon_key cannot be empty",
+            prefix
+        )));
+    }
+
+    if supabase.batch_size == 0 {
+        return Err(ConfigError::ValidationError(format!(
+            "{}.batch_size must be greater than 0",
+            prefix
+        )));
+    }
+
+    Ok(())
+}
+
 fn expand_env_vars(content: &str) -> String {
     let mut result = content.to_string();
 
@@ -187,6 +725,253 @@ fn expand_env_vars(content: &str) -> String {
 mod tests {
     use super::*;
 
+    const BASE: &str = r#"
+        [agent]
+        instance_id = "site-1"
+
+        [supabase]
+        url = "https://example.supabase.co"
+        anon_key = "anon"
+    "#;
+
+    fn parse(extra: &str) -> Result<AgentConfig, ConfigError> {
+        let mut config: AgentConfig = toml::from_str(&format!("{}{}", BASE, extra))
+            .map_err(|e| ConfigError::ParseError(e.to_string()))?;
+        config.validate()?;
+        config.normalize();
+        Ok(config)
+    }
+
+    #[test]
+    fn test_legacy_single_source() {
+        let config = parse(
+            r#"
+            [poi]
+            api_key = "poi-key"
+
+            [source]
+            type = "json"
+
+            [source.json]
+            path = "/tmp/reading.json"
+            json_path = "$.power"
+            unit = "kW"
+        "#,
+        )
+        .unwrap();
+
+        assert!(config.source.is_none());
+        assert_eq!(config.sources.len(), 1);
+        assert_eq!(config.sources[0].name, "default");
+        assert_eq!(config.api_key_for(&config.sources[0]), "poi-key");
+        assert_eq!(config.polling_interval_for(&config.sources[0]), 60);
+    }
+
+    #[test]
+    fn test_multiple_sources() {
+        let config = parse(
+            r#"
+            [[sources]]
+            name = "meter-a"
+            type = "csv"
+            api_key = "key-a"
+            polling_interval_secs = 10
+            csv = { path = "/tmp/a.csv", value_field = "power_kw", unit = "kW" }
+
+            [[sources]]
+            name = "meter-b"
+            type = "csv"
+            api_key = "key-b"
+            csv = { path = "/tmp/b.csv", value_field = "power_kw", unit = "MW", multiplier = 0.001 }
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.sources.len(), 2);
+        assert_eq!(config.api_key_for(&config.sources[1]), "key-b");
+        assert_eq!(config.polling_interval_for(&config.sources[0]), 10);
+        assert_eq!(config.polling_interval_for(&config.sources[1]), 60);
+    }
+
+    #[test]
+    fn test_input_unit_converts_to_poi_unit() {
+        let config = parse(
+            r#"
+            [poi]
+            unit = "MW"
+
+            [[sources]]
+            name = "meter-a"
+            type = "csv"
+            api_key = "key-a"
+            input_unit = "kW"
+            csv = { path = "/tmp/a.csv", value_field = "power_kw" }
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.sources[0].input_unit, Some(Unit::KW));
+        assert_eq!(config.sources[0].unit(), Some("MW"));
+
+        let err = parse(
+            r#"
+            [[sources]]
+            name = "meter-a"
+            type = "csv"
+            api_key = "key-a"
+            input_unit = "kWh"
+            csv = { path = "/tmp/a.csv", value_field = "energy", unit = "MW" }
+        "#,
+        )
+        .unwrap_err();
+
+        assert!(err
+            .to_string()
+            .contains("cannot convert kWh (energy) to MW (power)"));
+
+        // The old multiplier kept next to input_unit would convert twice
+        let err = parse(
+            r#"
+            [poi]
+            unit = "MW"
+
+            [[sources]]
+            name = "meter-a"
+            type = "csv"
+            api_key = "key-a"
+            input_unit = "kW"
+            csv = { path = "/tmp/a.csv", value_field = "power_kw", multiplier = 0.001 }
+        "#,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("remove the multiplier"));
+
+        // An energy register read as a counter reports power
+        let config = parse(
+            r#"
+            [[sources]]
+            name = "meter-a"
+            type = "modbus"
+            api_key = "key-a"
+            input_unit = "kWh"
+            counter = { rollover = 100000 }
+            modbus = { host = "10.0.0.5", address = 100, unit = "MW" }
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.sources[0].counter.as_ref().unwrap().rollover,
+            Some(100000.0)
+        );
+    }
+
+    #[test]
+    fn test_aggregate_window_covers_polling_interval() {
+        let source = |window: u64| {
+            format!(
+                r#"
+                [[sources]]
+                name = "meter-a"
+                type = "csv"
+                api_key = "key-a"
+                polling_interval_secs = 10
+                aggregate = {{ window_secs = {}, statistic = "max" }}
+                csv = {{ path = "/tmp/a.csv", value_field = "power_kw", unit = "kW" }}
+            "#,
+                window
+            )
+        };
+
+        let config = parse(&source(60)).unwrap();
+        let aggregate = config.sources[0].aggregate.as_ref().unwrap();
+        assert_eq!(aggregate.statistic, Statistic::Max);
+
+        let err = parse(&source(5)).unwrap_err();
+        assert!(err.to_string().contains("aggregate.window_secs"));
+    }
+
+    #[test]
+    fn test_fallbacks_name_sources_in_the_same_unit() {
+        let sources = |fallbacks: &str, cloud_unit: &str| {
+            format!(
+                r#"
+                [[sources]]
+                name = "local"
+                type = "csv"
+                api_key = "key-a"
+                fallbacks = [{}]
+                retry = {{ max_attempts = 3, initial_delay_ms = 200 }}
+                csv = {{ path = "/tmp/a.csv", value_field = "power_kw", unit = "kW" }}
+
+                [[sources]]
+                name = "cloud"
+                type = "csv"
+                api_key = "key-a"
+                csv = {{ path = "/tmp/b.csv", value_field = "power", unit = "{}" }}
+            "#,
+                fallbacks, cloud_unit
+            )
+        };
+
+        let config = parse(&sources(r#""cloud""#, "kW")).unwrap();
+        assert_eq!(config.sources[0].fallbacks, ["cloud"]);
+        assert_eq!(config.sources[0].retry.as_ref().unwrap().max_attempts, 3);
+
+        for (fallbacks, unit, expected) in [
+            (r#""cloud""#, "MW", "reports in MW"),
+            (r#""modem""#, "kW", "not a configured source"),
+            (r#""local""#, "kW", "the source itself"),
+        ] {
+            let err = parse(&sources(fallbacks, unit)).unwrap_err();
+            assert!(err.to_string().contains(expected), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_source_without_api_key_is_rejected() {
+        let err = parse(
+            r#"
+            [[sources]]
+            name = "meter-a"
+            type = "csv"
+            csv = { path = "/tmp/a.csv", value_field = "power_kw", unit = "kW" }
+        "#,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("sources.meter-a.api_key"));
+    }
+
+    #[test]
+    fn test_supabase_and_http_sinks() {
+        let config = parse(
+            r#"
+            [poi]
+            api_key = "poi-key"
+
+            [source]
+            type = "json"
+            json = { path = "/tmp/reading.json", json_path = "$.power", unit = "kW" }
+
+            [[sinks]]
+            name = "customer"
+            type = "http"
+
+            [sinks.http]
+            url = "https://example.com/ingest"
+            auth = { type = "bearer", token = "secret" }
+        "#,
+        )
+        .unwrap();
+
+        assert!(config.supabase.is_none());
+        assert_eq!(config.sinks.len(), 2);
+        assert_eq!(config.sinks[0].name, "supabase");
+        assert_eq!(config.sinks[0].sink_type, SinkType::Supabase);
+        assert_eq!(config.sinks[1].name, "customer");
+        assert_eq!(config.sinks[1].http.as_ref().unwrap().method, "POST");
+    }
+
     #[test]
     fn test_expand_env_vars() {
         std::env::set_var("TEST_VAR", "test_value");
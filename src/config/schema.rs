@@ -1,3 +1,4 @@
+use super::Unit;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -5,15 +6,27 @@ use std::path::PathBuf;
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct AgentConfig {
     pub agent: AgentSettings,
+    #[serde(default)]
     pub poi: PoiSettings,
-    pub supabase: SupabaseSettings,
-    pub source: SourceConfig,
+    /// Primary Supabase destination. Moved into `sinks` on load.
+    pub supabase: Option<SupabaseSettings>,
+    /// Additional destinations; every reading is delivered to each of them
+    #[serde(default)]
+    pub sinks: Vec<SinkConfig>,
+    /// Single-source form, kept for existing configurations. Moved into `sources` on load.
+    pub source: Option<SourceConfig>,
+    #[serde(default)]
+    pub sources: Vec<SourceConfig>,
     #[serde(default)]
     pub logging: LoggingSettings,
     #[serde(default)]
     pub update: UpdateSettings,
     #[serde(default)]
     pub retry: RetrySettings,
+    #[serde(default)]
+    pub outbox: OutboxSettings,
+    #[serde(default)]
+    pub health: HealthSettings,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -25,9 +38,13 @@ pub struct AgentSettings {
     pub verbose: bool,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct PoiSettings {
+    /// Default POI API key for sources that don't set their own
+    #[serde(default)]
     pub api_key: String,
+    /// Unit the POI expects, used by sources with an `input_unit` and no `unit` of their own
+    pub unit: Option<Unit>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -38,15 +55,107 @@ pub struct SupabaseSettings {
     pub rpc_endpoint: String,
     #[serde(default = "default_timeout")]
     pub timeout_secs: u64,
+    /// Send the reading's own timestamp as `p_timestamp` (the RPC function must accept it)
+    #[serde(default)]
+    pub send_timestamp: bool,
+    /// Endpoint accepting several readings per request. Unset to send one reading per request.
+    pub batch_endpoint: Option<String>,
+    #[serde(default)]
+    pub batch_mode: BatchMode,
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchMode {
+    /// RPC function taking `p_readings`, optionally returning one result per reading
+    #[default]
+    Rpc,
+    /// PostgREST table insert of an array of rows
+    Table,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SinkConfig {
+    #[serde(default)]
+    pub name: String,
+    #[serde(rename = "type")]
+    pub sink_type: SinkType,
+    pub supabase: Option<SupabaseSettings>,
+    pub http: Option<HttpSinkConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkType {
+    Supabase,
+    Http,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HttpSinkConfig {
+    pub url: String,
+    #[serde(default = "default_http_sink_method")]
+    pub method: String,
+    /// JSON body with `{value}`, `{unit}`, `{timestamp}`, `{api_key}` and `{source_id}` placeholders
+    #[serde(default = "default_http_sink_body_template")]
+    pub body_template: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    pub auth: Option<HttpAuthConfig>,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum HttpAuthConfig {
+    Basic {
+        username: String,
+        password: String,
+    },
+    Bearer {
+        token: String,
+    },
+    /// Client-credentials grant; the access token is cached and renewed before it expires
+    OAuth2 {
+        token_url: String,
+        client_id: String,
+        client_secret: String,
+        scope: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct SourceConfig {
+    #[serde(default)]
+    pub name: String,
     #[serde(rename = "type")]
     pub source_type: SourceType,
+    /// POI API key for this source, defaults to `poi.api_key`
+    pub api_key: Option<String>,
+    /// Defaults to `agent.polling_interval_secs`
+    pub polling_interval_secs: Option<u64>,
+    /// Unit the device reports in; readings are converted to the source's `unit`, or `poi.unit`
+    pub input_unit: Option<Unit>,
+    /// Treat the value as a cumulative energy register and report the average power between reads
+    pub counter: Option<CounterConfig>,
+    /// Checks readings must pass before they are sent
+    pub filter: Option<FilterConfig>,
+    /// Send one statistic per time window instead of every reading
+    pub aggregate: Option<AggregateConfig>,
+    /// Attempts per poll before the read counts as failed; one when unset
+    pub retry: Option<RetrySettings>,
+    /// Names of other sources read in this order when this one fails. A source
+    /// used as a fallback is only read through the source naming it.
+    #[serde(default)]
+    pub fallbacks: Vec<String>,
     pub csv: Option<CsvSourceConfig>,
     pub json: Option<JsonSourceConfig>,
     pub http: Option<HttpSourceConfig>,
+    pub modbus: Option<ModbusSourceConfig>,
+    pub mqtt: Option<MqttSourceConfig>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -55,12 +164,71 @@ pub enum SourceType {
     Csv,
     Json,
     Http,
+    Modbus,
+    Mqtt,
+}
+
+/// Power derived from a meter's energy register. Needs an energy `input_unit`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CounterConfig {
+    /// Register value at which the meter wraps back to zero, e.g. 100000 for a
+    /// five-digit register. Without it any decrease is treated as a reset.
+    pub rollover: Option<f64>,
+    /// Where the previous reading is kept between runs (defaults to the agent data directory)
+    pub state_file: Option<PathBuf>,
+}
+
+/// Readings outside these rules are dropped instead of sent. Values are compared
+/// as sent, after `multiplier` and unit conversion, except `ignore_values`. NaN and
+/// infinity are always dropped.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FilterConfig {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Largest change per second from the last accepted reading
+    pub max_rate_per_sec: Option<f64>,
+    /// Values a device writes when it has no measurement, e.g. 999999 or -1. Compared
+    /// with the value as read, before `multiplier`, unit conversion or a counter.
+    #[serde(default)]
+    pub ignore_values: Vec<f64>,
+}
+
+/// Readings are collected into windows aligned to the clock (a 60 second window
+/// runs from one minute to the next) and one value is sent per window.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AggregateConfig {
+    pub window_secs: u64,
+    #[serde(default)]
+    pub statistic: Statistic,
+    /// How long past its end a window waits for late readings before the clock
+    /// closes it (defaults to `window_secs`)
+    #[serde(default)]
+    pub grace_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Statistic {
+    #[default]
+    Mean,
+    Min,
+    Max,
+    Last,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct CsvSourceConfig {
+    /// A file, a glob such as `/data/power_*.csv`, or a directory when `file_pattern` is set
     pub path: PathBuf,
+    #[serde(default)]
     pub value_field: String,
+    /// Arithmetic over several columns instead of `value_field`, e.g. `sum(P1, P2, P3)`
+    pub expression: Option<String>,
+    /// Names used in `expression` mapped to column names or indexes, for columns
+    /// whose header isn't a plain identifier
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    #[serde(default)]
     pub unit: String,
     #[serde(default = "default_true")]
     pub read_last_row: bool,
@@ -70,15 +238,72 @@ pub struct CsvSourceConfig {
     pub skip_headers: usize,
     #[serde(default = "default_multiplier_one")]
     pub multiplier: f64,  // Ex: 0.001 pour convertir kW en MW
+    #[serde(default)]
+    pub mode: CsvReadMode,
+    /// Where incremental mode saves its position (defaults to the agent data directory)
+    pub cursor_file: Option<PathBuf>,
+    /// Column holding the measurement time (name or index); the read time is used when unset
+    pub timestamp_field: Option<String>,
+    /// `iso` (default), `epoch`, `epoch_ms` or a strftime pattern
+    pub timestamp_format: Option<String>,
+    /// Timezone of timestamps written without an offset (IANA name or `+01:00`), UTC by default
+    pub timezone: Option<String>,
+    /// Report the source as stale when the file, or its latest row timestamp, is older than this
+    pub max_age_secs: Option<u64>,
+    /// Don't send the same reading again when nothing was written since the previous poll
+    #[serde(default)]
+    pub suppress_unchanged: bool,
+    /// Glob for file names inside the `path` directory, e.g. `power_*.csv`
+    pub file_pattern: Option<String>,
+    /// How the newest of several matching files is chosen
+    #[serde(default)]
+    pub newest_by: NewestBy,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CsvReadMode {
+    /// Parse the whole file on every poll
+    #[default]
+    Full,
+    /// Follow an append-only file, parsing only rows added since the last poll
+    Tail,
+    /// Like tail, but send every new row rather than only the last one
+    Incremental,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NewestBy {
+    /// Last in name order, for files named after their date
+    #[default]
+    Name,
+    /// Most recently modified
+    Modified,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct JsonSourceConfig {
     pub path: PathBuf,
+    #[serde(default)]
     pub json_path: String,
+    /// Arithmetic over several fields instead of `json_path`, e.g. `V * I / 1000`
+    pub expression: Option<String>,
+    /// Names used in `expression` mapped to JSONPaths (default `$.<name>`)
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    #[serde(default)]
     pub unit: String,
     #[serde(default = "default_multiplier_one")]
     pub multiplier: f64,
+    /// JSONPath of the measurement time; the read time is used when unset
+    pub timestamp_path: Option<String>,
+    pub timestamp_format: Option<String>,
+    pub timezone: Option<String>,
+    /// Report the source as stale when the file, or its timestamp, is older than this
+    pub max_age_secs: Option<u64>,
+    #[serde(default)]
+    pub suppress_unchanged: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -86,17 +311,144 @@ pub struct HttpSourceConfig {
     pub url: String,
     #[serde(default = "default_http_method")]
     pub method: String,
+    /// How the response body is read
+    #[serde(default)]
+    pub format: HttpResponseFormat,
+    /// JSONPath of the value, for `json` responses
+    #[serde(default)]
     pub json_path: String,
+    /// Column name or 0-based index of the value, for `csv` responses (last row is used)
+    #[serde(default)]
+    pub value_field: String,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: String,
+    /// Element or attribute holding the value, for `xml` responses, e.g. `//Value[@name='Pac']`
+    pub xml_path: Option<String>,
+    /// Regular expression whose first capture group is the value, for `regex` responses
+    pub pattern: Option<String>,
+    /// Arithmetic over several fields instead of a single value
+    pub expression: Option<String>,
+    /// Names used in `expression` mapped to JSONPaths (default `$.<name>`),
+    /// CSV columns (default the name) or XML paths (default `//<name>`)
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    #[serde(default)]
     pub unit: String,
     #[serde(default)]
     pub headers: HashMap<String, String>,
+    /// Query parameters; values may use time placeholders such as `{now}` or `{now-5m}`
+    #[serde(default)]
+    pub query: HashMap<String, String>,
+    /// Request body, with the same time placeholders as `query`
+    pub body_template: Option<String>,
+    pub auth: Option<HttpAuthConfig>,
     #[serde(default = "default_http_timeout")]
     pub timeout_secs: u64,
     #[serde(default = "default_multiplier_one")]
     pub multiplier: f64,
+    /// Where the measurement time is: a JSONPath, CSV column or XML path, or
+    /// the `timestamp` group of `pattern`. The read time is used when unset.
+    pub timestamp_path: Option<String>,
+    pub timestamp_format: Option<String>,
+    pub timezone: Option<String>,
+    /// Report the source as stale when its data (timestamp, or `Last-Modified`) is older than this
+    pub max_age_secs: Option<u64>,
+    /// Don't send the same reading again when the server answers 304 Not Modified
+    #[serde(default)]
+    pub suppress_unchanged: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpResponseFormat {
+    #[default]
+    Json,
+    Csv,
+    Xml,
+    /// Plain text searched with `pattern`
+    Regex,
+    /// A body that is just a number
+    Number,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ModbusSourceConfig {
+    pub host: String,
+    #[serde(default = "default_modbus_port")]
+    pub port: u16,
+    #[serde(default = "default_modbus_unit_id")]
+    pub unit_id: u8,
+    /// Zero-based register address
+    pub address: u16,
+    #[serde(default)]
+    pub register_type: ModbusRegisterType,
+    #[serde(default)]
+    pub data_type: ModbusDataType,
+    /// Order of the two registers of a 32-bit value
+    #[serde(default)]
+    pub word_order: ModbusWordOrder,
+    #[serde(default)]
+    pub unit: String,
+    #[serde(default = "default_multiplier_one")]
+    pub multiplier: f64,
+    #[serde(default = "default_modbus_timeout")]
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModbusRegisterType {
+    #[default]
+    Holding,
+    Input,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModbusDataType {
+    #[default]
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModbusWordOrder {
+    /// High word first
+    #[default]
+    Big,
+    /// Low word first
+    Little,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MqttSourceConfig {
+    pub host: String,
+    #[serde(default = "default_mqtt_port")]
+    pub port: u16,
+    pub topic: String,
+    /// JSONPath into the message payload. When unset the payload must be a bare number.
+    pub json_path: Option<String>,
+    #[serde(default)]
+    pub unit: String,
+    #[serde(default = "default_multiplier_one")]
+    pub multiplier: f64,
+    /// Defaults to a name derived from the agent and topic
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// 0, 1 or 2
+    #[serde(default)]
+    pub qos: u8,
+    /// Report the source as stale when no message arrived within this many polling intervals
+    #[serde(default = "default_mqtt_stale_after")]
+    pub stale_after_intervals: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct LoggingSettings {
     #[serde(default = "default_log_level")]
     pub level: String,
@@ -176,6 +528,47 @@ impl Default for RetrySettings {
     }
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OutboxSettings {
+    /// Persist unsent readings to disk so they survive restarts
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub directory: Option<PathBuf>,
+    #[serde(default = "default_outbox_max_entries")]
+    pub max_entries: usize,
+    #[serde(default = "default_outbox_max_age")]
+    pub max_age_hours: u64,
+}
+
+impl Default for OutboxSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            directory: None,
+            max_entries: default_outbox_max_entries(),
+            max_age_hours: default_outbox_max_age(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct HealthSettings {
+    /// Serve `/healthz` and Prometheus `/metrics` over HTTP
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_health_bind")]
+    pub bind: String,
+}
+
+impl Default for HealthSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: default_health_bind(),
+        }
+    }
+}
+
 // Default value functions
 fn default_polling_interval() -> u64 {
     60
@@ -189,6 +582,10 @@ fn default_timeout() -> u64 {
     30
 }
 
+fn default_batch_size() -> usize {
+    100
+}
+
 fn default_true() -> bool {
     true
 }
@@ -205,6 +602,35 @@ fn default_http_timeout() -> u64 {
     10
 }
 
+fn default_modbus_port() -> u16 {
+    502
+}
+
+fn default_modbus_unit_id() -> u8 {
+    1
+}
+
+fn default_modbus_timeout() -> u64 {
+    5
+}
+
+fn default_mqtt_port() -> u16 {
+    1883
+}
+
+fn default_mqtt_stale_after() -> u32 {
+    3
+}
+
+fn default_http_sink_method() -> String {
+    "POST".to_string()
+}
+
+fn default_http_sink_body_template() -> String {
+    r#"{"api_key": "{api_key}", "value": {value}, "unit": "{unit}", "timestamp": "{timestamp}", "source_id": "{source_id}"}"#
+        .to_string()
+}
+
 fn default_log_level() -> String {
     "info".to_string()
 }
@@ -240,3 +666,15 @@ fn default_multiplier() -> f64 {
 fn default_multiplier_one() -> f64 {
     1.0
 }
+
+fn default_health_bind() -> String {
+    "127.0.0.1:9898".to_string()
+}
+
+fn default_outbox_max_entries() -> usize {
+    100_000
+}
+
+fn default_outbox_max_age() -> u64 {
+    168
+}
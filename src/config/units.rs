@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Units the agent knows how to convert between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Unit {
+    W,
+    KW,
+    MW,
+    Wh,
+    KWh,
+    MWh,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantity {
+    Power,
+    Energy,
+}
+
+impl Unit {
+    pub fn quantity(self) -> Quantity {
+        match self {
+            Unit::W | Unit::KW | Unit::MW => Quantity::Power,
+            Unit::Wh | Unit::KWh | Unit::MWh => Quantity::Energy,
+        }
+    }
+
+    /// The power unit matching an energy unit, e.g. kW for kWh.
+    pub fn power(self) -> Unit {
+        match self {
+            Unit::Wh => Unit::W,
+            Unit::KWh => Unit::KW,
+            Unit::MWh => Unit::MW,
+            power => power,
+        }
+    }
+
+    /// Size of the unit in W or Wh
+    fn scale(self) -> f64 {
+        match self {
+            Unit::W | Unit::Wh => 1.0,
+            Unit::KW | Unit::KWh => 1e3,
+            Unit::MW | Unit::MWh => 1e6,
+        }
+    }
+
+    /// What to multiply a value in this unit by to express it in `target`,
+    /// or `None` when one is power and the other energy.
+    pub fn factor_to(self, target: Unit) -> Option<f64> {
+        (self.quantity() == target.quantity()).then(|| self.scale() / target.scale())
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Unit::W => "W",
+            Unit::KW => "kW",
+            Unit::MW => "MW",
+            Unit::Wh => "Wh",
+            Unit::KWh => "kWh",
+            Unit::MWh => "MWh",
+        })
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Quantity::Power => "power",
+            Quantity::Energy => "energy",
+        })
+    }
+}
+
+impl FromStr for Unit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Case-insensitive: nobody means milliwatts here
+        match s.trim().to_ascii_lowercase().as_str() {
+            "w" => Ok(Unit::W),
+            "kw" => Ok(Unit::KW),
+            "mw" => Ok(Unit::MW),
+            "wh" => Ok(Unit::Wh),
+            "kwh" => Ok(Unit::KWh),
+            "mwh" => Ok(Unit::MWh),
+            _ => Err(format!(
+                "unknown unit '{}' (expected W, kW, MW, Wh, kWh or MWh)",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Unit {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Unit> for String {
+    fn from(unit: Unit) -> Self {
+        unit.to_string()
+    }
+}
@@ -11,6 +11,12 @@ pub enum AgentError {
     #[error("Transport error: {0}")]
     Transport(#[from] TransportError),
 
+    #[error("Outbox error: {0}")]
+    Outbox(#[from] OutboxError),
+
+    #[error("Health endpoint error: {0}")]
+    Health(#[from] HealthError),
+
     #[error("Update error: {0}")]
     Update(#[from] UpdateError),
 
@@ -47,6 +53,12 @@ pub enum SourceError {
     #[error("Value not found at path: {0}")]
     ValueNotFound(String),
 
+    #[error("Column not found: {0}")]
+    ColumnNotFound(String),
+
+    #[error("Invalid path: {0}")]
+    InvalidPath(String),
+
     #[error("Invalid value type: expected number, got {0}")]
     InvalidValueType(String),
 
@@ -58,6 +70,64 @@ pub enum SourceError {
 
     #[error("JSON error: {0}")]
     JsonError(String),
+
+    #[error("XML error: {0}")]
+    XmlError(String),
+
+    #[error("Modbus error: {0}")]
+    ModbusError(String),
+
+    #[error("Invalid timestamp: {0}")]
+    InvalidTimestamp(String),
+
+    #[error("Stale data: {0}")]
+    Stale(String),
+
+    #[error("State file error: {0}")]
+    StateError(String),
+
+    #[error("Rejected reading: {0}")]
+    Rejected(String),
+
+    #[error("HTTP {status}: {message}")]
+    HttpStatus { status: u16, message: String },
+
+    #[error(
+        "Rate limited by the source, retry after {}",
+        .0.map_or("an unspecified delay".to_string(), |secs| format!("{} seconds", secs))
+    )]
+    RateLimited(Option<u64>),
+}
+
+impl SourceError {
+    /// Errors that repeat on every read until the configuration or the device
+    /// changes, like a column or an endpoint that doesn't exist.
+    ///
+    /// A value missing from one particular document or row is not permanent:
+    /// an empty array at night or a half-written line reads fine later.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            Self::ColumnNotFound(_) | Self::InvalidPath(_) => true,
+            Self::HttpStatus { status, .. } => matches!(status, 400 | 404 | 405 | 410),
+            _ => false,
+        }
+    }
+}
+
+impl Retriable for SourceError {
+    /// Stale data, a rate limit, a rejected value or a setting that can't be
+    /// parsed won't be different a moment later.
+    fn is_retriable(&self) -> bool {
+        !self.is_permanent()
+            && !matches!(
+                self,
+                Self::ParseError(_)
+                    | Self::InvalidValueType(_)
+                    | Self::Stale(_)
+                    | Self::RateLimited(_)
+                    | Self::Rejected(_)
+            )
+    }
 }
 
 #[derive(Debug, Error)]
@@ -68,8 +138,11 @@ pub enum TransportError {
     #[error("Authentication failed: {0}")]
     AuthFailed(String),
 
-    #[error("Rate limited, retry after {0} seconds")]
-    RateLimited(u64),
+    #[error(
+        "Rate limited, retry after {}",
+        .0.map_or("an unspecified delay".to_string(), |secs| format!("{} seconds", secs))
+    )]
+    RateLimited(Option<u64>),
 
     #[error("Server error (status {status}): {message}")]
     ServerError { status: u16, message: String },
@@ -79,16 +152,51 @@ pub enum TransportError {
 
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
+
+    #[error("Invalid transport configuration: {0}")]
+    InvalidConfig(String),
 }
 
-impl TransportError {
-    pub fn is_retriable(&self) -> bool {
+impl Retriable for TransportError {
+    fn is_retriable(&self) -> bool {
         match self {
             Self::Network(_) | Self::RateLimited(_) | Self::Timeout => true,
             Self::ServerError { status, .. } => *status >= 500,
             _ => false,
         }
     }
+
+    fn retry_after(&self) -> Option<std::time::Duration> {
+        match self {
+            Self::RateLimited(Some(secs)) => Some(std::time::Duration::from_secs(*secs)),
+            _ => None,
+        }
+    }
+}
+
+/// How `with_retry` decides whether, and when, to try an operation again.
+pub trait Retriable: std::fmt::Display {
+    fn is_retriable(&self) -> bool;
+
+    /// Minimum wait the other side asked for before the next attempt.
+    fn retry_after(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum OutboxError {
+    #[error("Failed to open outbox: {0}")]
+    OpenFailed(String),
+
+    #[error("Failed to write outbox: {0}")]
+    WriteFailed(String),
+}
+
+#[derive(Debug, Error)]
+pub enum HealthError {
+    #[error("Failed to bind health endpoint: {0}")]
+    BindFailed(String),
 }
 
 #[derive(Debug, Error)]
@@ -117,15 +225,6 @@ pub enum ServiceError {
     #[error("Failed to uninstall service: {0}")]
     UninstallFailed(String),
 
-    #[error("Failed to start service: {0}")]
-    StartFailed(String),
-
-    #[error("Failed to stop service: {0}")]
-    StopFailed(String),
-
     #[error("Service not found")]
     NotFound,
-
-    #[error("Permission denied: {0}")]
-    PermissionDenied(String),
 }
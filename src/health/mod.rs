@@ -0,0 +1,516 @@
+pub mod server;
+pub mod status;
+
+pub use server::serve;
+pub use status::{AgentStatus, DisabledSource};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SourceStats {
+    pub reads: u64,
+    pub read_failures: u64,
+    /// Readings rejected by the source's value filter
+    pub dropped: u64,
+    /// Readings that arrived after their aggregation window was sent
+    pub late_dropped: u64,
+    pub last_value: Option<f64>,
+    pub unit: String,
+    pub last_read_at: Option<DateTime<Utc>>,
+    /// Whether the most recent read succeeded
+    pub last_read_ok: bool,
+    /// Set when the source was stopped after a permanent error
+    pub disabled: Option<DisabledSource>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct SinkStats {
+    pub sends: u64,
+    pub send_failures: u64,
+    pub retries: u64,
+    pub pending: usize,
+    pub last_send_at: Option<DateTime<Utc>>,
+    /// Whether the most recent delivery attempt succeeded
+    pub last_send_ok: bool,
+}
+
+/// Counters shared between the scheduler and the health endpoint.
+///
+/// Outlives any single scheduler so values are kept across restarts of the pipeline.
+pub struct Metrics {
+    started_at: DateTime<Utc>,
+    sources: Mutex<BTreeMap<String, SourceStats>>,
+    sinks: Mutex<BTreeMap<String, SinkStats>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub uptime_secs: i64,
+    pub sources: BTreeMap<String, SourceStats>,
+    pub sinks: BTreeMap<String, SinkStats>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            sources: Mutex::new(BTreeMap::new()),
+            sinks: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn record_read(&self, source: &str, value: f64, unit: &str) {
+        let mut sources = self.sources.lock().unwrap();
+        let stats = sources.entry(source.to_string()).or_default();
+        stats.reads += 1;
+        stats.last_value = Some(value);
+        stats.unit = unit.to_string();
+        stats.last_read_at = Some(Utc::now());
+        stats.last_read_ok = true;
+    }
+
+    pub fn record_read_failure(&self, source: &str) {
+        let mut sources = self.sources.lock().unwrap();
+        let stats = sources.entry(source.to_string()).or_default();
+        stats.read_failures += 1;
+        stats.last_read_ok = false;
+    }
+
+    pub fn record_dropped(&self, source: &str) {
+        let mut sources = self.sources.lock().unwrap();
+        sources.entry(source.to_string()).or_default().dropped += 1;
+    }
+
+    pub fn record_late_dropped(&self, source: &str) {
+        let mut sources = self.sources.lock().unwrap();
+        sources.entry(source.to_string()).or_default().late_dropped += 1;
+    }
+
+    pub fn record_disabled(&self, source: &str, error: &str) {
+        let mut sources = self.sources.lock().unwrap();
+        sources.entry(source.to_string()).or_default().disabled = Some(DisabledSource {
+            error: error.to_string(),
+            disabled_at: Utc::now(),
+        });
+    }
+
+    /// Re-enable every source, as a new scheduler starts them all again.
+    pub fn clear_disabled(&self) {
+        for stats in self.sources.lock().unwrap().values_mut() {
+            stats.disabled = None;
+        }
+    }
+
+    /// The disabled sources, for `agentquelia status`.
+    pub fn status(&self) -> AgentStatus {
+        AgentStatus {
+            disabled_sources: self
+                .sources
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|(name, stats)| Some((name.clone(), stats.disabled.clone()?)))
+                .collect(),
+        }
+    }
+
+    pub fn record_sent(&self, sink: &str, readings: u64) {
+        let mut sinks = self.sinks.lock().unwrap();
+        let stats = sinks.entry(sink.to_string()).or_default();
+        stats.sends += readings;
+        stats.last_send_at = Some(Utc::now());
+        stats.last_send_ok = true;
+    }
+
+    pub fn record_send_failure(&self, sink: &str) {
+        let mut sinks = self.sinks.lock().unwrap();
+        let stats = sinks.entry(sink.to_string()).or_default();
+        stats.send_failures += 1;
+        stats.last_send_ok = false;
+    }
+
+    pub fn record_retries(&self, sink: &str, retries: u64) {
+        if retries > 0 {
+            let mut sinks = self.sinks.lock().unwrap();
+            sinks.entry(sink.to_string()).or_default().retries += retries;
+        }
+    }
+
+    pub fn set_pending(&self, sink: &str, pending: usize) {
+        let mut sinks = self.sinks.lock().unwrap();
+        sinks.entry(sink.to_string()).or_default().pending = pending;
+    }
+
+    /// Forget sources and sinks that are no longer configured.
+    pub fn retain(&self, sources: &[&str], sinks: &[&str]) {
+        self.sources
+            .lock()
+            .unwrap()
+            .retain(|name, _| sources.contains(&name.as_str()));
+        self.sinks
+            .lock()
+            .unwrap()
+            .retain(|name, _| sinks.contains(&name.as_str()));
+    }
+
+    /// Healthy when no source and no sink is failing, judged by each one's most
+    /// recent attempt. Components that haven't done anything yet count as healthy.
+    /// Which source is failing shows in the per-source stats of the report.
+    pub fn health(&self) -> HealthReport {
+        let sources = self.sources.lock().unwrap().clone();
+        let sinks = self.sinks.lock().unwrap().clone();
+
+        let reading = sources
+            .values()
+            .all(|s| s.last_read_ok || s.reads + s.read_failures == 0);
+        let delivering = sinks
+            .values()
+            .all(|s| s.last_send_ok || s.sends + s.send_failures == 0);
+
+        HealthReport {
+            healthy: reading && delivering,
+            uptime_secs: (Utc::now() - self.started_at).num_seconds(),
+            sources,
+            sinks,
+        }
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn render_prometheus(&self) -> String {
+        let sources = self.sources.lock().unwrap().clone();
+        let sinks = self.sinks.lock().unwrap().clone();
+        let mut out = String::new();
+
+        metric(
+            &mut out,
+            "agentquelia_uptime_seconds",
+            "gauge",
+            "Seconds since the agent started.",
+        );
+        let _ = writeln!(
+            out,
+            "agentquelia_uptime_seconds {}",
+            (Utc::now() - self.started_at).num_seconds()
+        );
+
+        metric(
+            &mut out,
+            "agentquelia_source_reads_total",
+            "counter",
+            "Successful reads per source.",
+        );
+        for (name, stats) in &sources {
+            sample(
+                &mut out,
+                "agentquelia_source_reads_total",
+                "source",
+                name,
+                stats.reads,
+            );
+        }
+
+        metric(
+            &mut out,
+            "agentquelia_source_read_failures_total",
+            "counter",
+            "Failed reads per source.",
+        );
+        for (name, stats) in &sources {
+            sample(
+                &mut out,
+                "agentquelia_source_read_failures_total",
+                "source",
+                name,
+                stats.read_failures,
+            );
+        }
+
+        metric(
+            &mut out,
+            "agentquelia_source_dropped_total",
+            "counter",
+            "Readings rejected by the value filter per source.",
+        );
+        for (name, stats) in &sources {
+            sample(
+                &mut out,
+                "agentquelia_source_dropped_total",
+                "source",
+                name,
+                stats.dropped,
+            );
+        }
+
+        metric(
+            &mut out,
+            "agentquelia_source_late_dropped_total",
+            "counter",
+            "Readings dropped because their aggregation window was already sent, per source.",
+        );
+        for (name, stats) in &sources {
+            sample(
+                &mut out,
+                "agentquelia_source_late_dropped_total",
+                "source",
+                name,
+                stats.late_dropped,
+            );
+        }
+
+        metric(
+            &mut out,
+            "agentquelia_source_disabled",
+            "gauge",
+            "1 when the source was stopped after a permanent error.",
+        );
+        for (name, stats) in &sources {
+            sample(
+                &mut out,
+                "agentquelia_source_disabled",
+                "source",
+                name,
+                u8::from(stats.disabled.is_some()),
+            );
+        }
+
+        metric(
+            &mut out,
+            "agentquelia_source_last_value",
+            "gauge",
+            "Most recent value read from each source.",
+        );
+        for (name, stats) in &sources {
+            if let Some(value) = stats.last_value {
+                let _ = writeln!(
+                    out,
+                    "agentquelia_source_last_value{{source=\"{}\",unit=\"{}\"}} {}",
+                    escape_label(name),
+                    escape_label(&stats.unit),
+                    value
+                );
+            }
+        }
+
+        metric(
+            &mut out,
+            "agentquelia_source_last_read_timestamp_seconds",
+            "gauge",
+            "Unix time of the last successful read per source.",
+        );
+        for (name, stats) in &sources {
+            if let Some(at) = stats.last_read_at {
+                sample(
+                    &mut out,
+                    "agentquelia_source_last_read_timestamp_seconds",
+                    "source",
+                    name,
+                    at.timestamp(),
+                );
+            }
+        }
+
+        metric(
+            &mut out,
+            "agentquelia_sink_sends_total",
+            "counter",
+            "Readings delivered per sink.",
+        );
+        for (name, stats) in &sinks {
+            sample(
+                &mut out,
+                "agentquelia_sink_sends_total",
+                "sink",
+                name,
+                stats.sends,
+            );
+        }
+
+        metric(
+            &mut out,
+            "agentquelia_sink_send_failures_total",
+            "counter",
+            "Deliveries that failed after retries, or were rejected, per sink.",
+        );
+        for (name, stats) in &sinks {
+            sample(
+                &mut out,
+                "agentquelia_sink_send_failures_total",
+                "sink",
+                name,
+                stats.send_failures,
+            );
+        }
+
+        metric(
+            &mut out,
+            "agentquelia_sink_retries_total",
+            "counter",
+            "Request retries per sink.",
+        );
+        for (name, stats) in &sinks {
+            sample(
+                &mut out,
+                "agentquelia_sink_retries_total",
+                "sink",
+                name,
+                stats.retries,
+            );
+        }
+
+        metric(
+            &mut out,
+            "agentquelia_sink_pending_readings",
+            "gauge",
+            "Readings waiting in the outbox per sink.",
+        );
+        for (name, stats) in &sinks {
+            sample(
+                &mut out,
+                "agentquelia_sink_pending_readings",
+                "sink",
+                name,
+                stats.pending,
+            );
+        }
+
+        metric(
+            &mut out,
+            "agentquelia_sink_last_send_timestamp_seconds",
+            "gauge",
+            "Unix time of the last successful delivery per sink.",
+        );
+        for (name, stats) in &sinks {
+            if let Some(at) = stats.last_send_at {
+                sample(
+                    &mut out,
+                    "agentquelia_sink_last_send_timestamp_seconds",
+                    "sink",
+                    name,
+                    at.timestamp(),
+                );
+            }
+        }
+
+        out
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, label: &str, value: &str, sample: impl std::fmt::Display) {
+    let _ = writeln!(
+        out,
+        "{}{{{}=\"{}\"}} {}",
+        name,
+        label,
+        escape_label(value),
+        sample
+    );
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_prometheus() {
+        let metrics = Metrics::new();
+        metrics.record_read("meter \"a\"", 12.5, "kW");
+        metrics.record_read_failure("meter \"a\"");
+        metrics.record_dropped("meter \"a\"");
+        metrics.record_late_dropped("meter \"a\"");
+        metrics.record_late_dropped("meter \"a\"");
+        metrics.record_sent("supabase", 3);
+        metrics.record_retries("supabase", 2);
+        metrics.set_pending("supabase", 4);
+
+        let text = metrics.render_prometheus();
+        assert!(text.contains("agentquelia_source_reads_total{source=\"meter \\\"a\\\"\"} 1"));
+        assert!(
+            text.contains("agentquelia_source_read_failures_total{source=\"meter \\\"a\\\"\"} 1")
+        );
+        assert!(text.contains("agentquelia_source_dropped_total{source=\"meter \\\"a\\\"\"} 1"));
+        assert!(
+            text.contains("agentquelia_source_late_dropped_total{source=\"meter \\\"a\\\"\"} 2")
+        );
+        assert!(text.contains(
+            "agentquelia_source_last_value{source=\"meter \\\"a\\\"\",unit=\"kW\"} 12.5"
+        ));
+        assert!(text.contains("agentquelia_sink_sends_total{sink=\"supabase\"} 3"));
+        assert!(text.contains("agentquelia_sink_retries_total{sink=\"supabase\"} 2"));
+        assert!(text.contains("agentquelia_sink_pending_readings{sink=\"supabase\"} 4"));
+        assert!(text.contains("# TYPE agentquelia_sink_send_failures_total counter"));
+    }
+
+    #[test]
+    fn test_disabled_sources_in_status() {
+        let metrics = Metrics::new();
+        metrics.record_read("a", 1.0, "kW");
+        metrics.record_disabled("b", "Column 'P' not found in CSV");
+
+        let status = metrics.status();
+        assert_eq!(status.disabled_sources.len(), 1);
+        assert_eq!(
+            status.disabled_sources["b"].error,
+            "Column 'P' not found in CSV"
+        );
+        assert!(metrics
+            .render_prometheus()
+            .contains("agentquelia_source_disabled{source=\"b\"} 1"));
+
+        metrics.clear_disabled();
+        assert!(metrics.status().disabled_sources.is_empty());
+    }
+
+    #[test]
+    fn test_health_follows_last_outcome() {
+        let metrics = Metrics::new();
+        assert!(metrics.health().healthy);
+
+        metrics.record_read_failure("a");
+        assert!(!metrics.health().healthy);
+        metrics.record_read("a", 1.0, "kW");
+        assert!(metrics.health().healthy);
+
+        metrics.record_send_failure("supabase");
+        assert!(!metrics.health().healthy);
+        metrics.record_sent("supabase", 1);
+        assert!(metrics.health().healthy);
+    }
+
+    #[test]
+    fn test_health_fails_when_any_source_fails() {
+        let metrics = Metrics::new();
+        metrics.record_read("a", 1.0, "kW");
+        metrics.record_read("b", 2.0, "kW");
+        metrics.record_dropped("c");
+        assert!(metrics.health().healthy);
+
+        metrics.record_read_failure("b");
+        let report = metrics.health();
+        assert!(!report.healthy);
+        assert!(report.sources["a"].last_read_ok);
+        assert!(!report.sources["b"].last_read_ok);
+    }
+}
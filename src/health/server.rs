@@ -0,0 +1,151 @@
+use super::Metrics;
+use crate::error::HealthError;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, info};
+
+/// Requests larger than this are not health checks
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+/// Slow clients are dropped so they can't pile up connections
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bind `bind` and serve `/healthz` and `/metrics` from a background task.
+pub async fn serve(bind: &str, metrics: Arc<Metrics>) -> Result<JoinHandle<()>, HealthError> {
+    let listener = TcpListener::bind(bind)
+        .await
+        .map_err(|e| HealthError::BindFailed(format!("{}: {}", bind, e)))?;
+    info!(address = %bind, "Health endpoint listening");
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let Ok((socket, peer)) = listener.accept().await else {
+                continue;
+            };
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                if let Err(e) =
+                    tokio::time::timeout(REQUEST_TIMEOUT, handle(socket, &metrics)).await
+                {
+                    debug!(peer = %peer, error = %e, "Health request timed out");
+                }
+            });
+        }
+    }))
+}
+
+async fn handle(mut socket: TcpStream, metrics: &Metrics) {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST_BYTES {
+        match socket.read(&mut buf).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => request.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    // Ignore any query string
+    let path = parts
+        .next()
+        .unwrap_or_default()
+        .split('?')
+        .next()
+        .unwrap_or_default();
+
+    let (status, content_type, body) = route(method, path, metrics);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    let _ = socket.write_all(response.as_bytes()).await;
+    let _ = socket.shutdown().await;
+}
+
+fn route(method: &str, path: &str, metrics: &Metrics) -> (&'static str, &'static str, String) {
+    if method != "GET" {
+        return (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed\n".to_string(),
+        );
+    }
+
+    match path {
+        "/healthz" => {
+            let report = metrics.health();
+            let status = if report.healthy {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            let body = serde_json::to_string_pretty(&report).unwrap_or_default();
+            (status, "application/json", body)
+        }
+        "/metrics" => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            metrics.render_prometheus(),
+        ),
+        _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(port: u16, path: &str) -> String {
+        let mut socket = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        socket
+            .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        socket.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_serves_health_and_metrics() {
+        // Find a free port, then hand it to the server
+        let port = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let metrics = Arc::new(Metrics::new());
+        metrics.record_read("meter", 4.2, "kW");
+        let task = serve(&format!("127.0.0.1:{}", port), metrics.clone())
+            .await
+            .unwrap();
+
+        let health = get(port, "/healthz").await;
+        assert!(health.starts_with("HTTP/1.1 200 OK"));
+        assert!(health.contains("\"healthy\": true"));
+
+        let text = get(port, "/metrics").await;
+        assert!(text.contains("agentquelia_source_reads_total{source=\"meter\"} 1"));
+
+        metrics.record_send_failure("supabase");
+        assert!(get(port, "/healthz")
+            .await
+            .starts_with("HTTP/1.1 503 Service Unavailable"));
+        assert!(get(port, "/nope").await.starts_with("HTTP/1.1 404"));
+
+        task.abort();
+    }
+}
@@ -0,0 +1,90 @@
+use crate::config::AgentConfig;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// What the running agent reports to `agentquelia status` through a status file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AgentStatus {
+    /// Sources no longer read after a permanent error, until the configuration is reloaded
+    pub disabled_sources: BTreeMap<String, DisabledSource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DisabledSource {
+    pub error: String,
+    pub disabled_at: DateTime<Utc>,
+}
+
+impl AgentStatus {
+    /// Where this agent writes its status: `AGENTQUELIA_STATUS_FILE` when set, as the
+    /// installed service does, the data directory otherwise.
+    pub fn path() -> Option<PathBuf> {
+        std::env::var_os("AGENTQUELIA_STATUS_FILE")
+            .map(PathBuf::from)
+            .or_else(|| AgentConfig::default_data_dir().map(|p| p.join("status.json")))
+    }
+
+    /// Every file a running agent may report through: this user's own, and the
+    /// service's, which runs as another user.
+    fn paths() -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = Self::path()
+            .into_iter()
+            .chain(Self::service_path())
+            .collect();
+        paths.dedup();
+        paths
+    }
+
+    fn service_path() -> Option<PathBuf> {
+        #[cfg(target_os = "linux")]
+        {
+            Some(PathBuf::from(crate::service::linux::STATUS_FILE))
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+
+    /// The status the agents last wrote; empty when they have nothing to report.
+    /// Fails when a status file exists but this user isn't allowed to read it.
+    pub fn load() -> Result<Self, String> {
+        let mut status = Self::default();
+        for path in Self::paths() {
+            let content = match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("Cannot read {}: {}", path.display(), e)),
+            };
+            if let Ok(saved) = serde_json::from_str::<Self>(&content) {
+                status.disabled_sources.extend(saved.disabled_sources);
+            }
+        }
+        Ok(status)
+    }
+
+    /// Write the status file, or remove it when there is nothing to report.
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+
+        if self.disabled_sources.is_empty() {
+            return match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_string_pretty(self)?;
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(&tmp_path, &path)
+    }
+}
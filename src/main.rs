@@ -1,10 +1,14 @@
 mod cli;
 mod config;
 mod error;
+mod health;
 mod logging;
+mod outbox;
 mod scheduler;
 mod service;
 mod sources;
+#[cfg(test)]
+mod test_server;
 mod transport;
 mod update;
 
@@ -54,7 +58,7 @@ async fn run_agent(cli: Cli) -> Result<(), AgentError> {
     );
 
     // Create and run the agent
-    let runner = AgentRunner::new(config);
+    let runner = AgentRunner::new(config, AgentConfig::resolve_path(cli.config.as_deref()));
     runner.run().await?;
 
     info!("Agentquelia stopped");
@@ -151,51 +155,189 @@ fn show_config(cli: Cli, show_secrets: bool) -> Result<(), AgentError> {
     );
     println!();
 
-    println!("POI:");
-    if show_secrets {
-        println!("  API Key: {}", config.poi.api_key);
-    } else {
-        println!("  API Key: {}...", &config.poi.api_key[..12.min(config.poi.api_key.len())]);
-    }
-    println!();
-
-    println!("Supabase:");
-    println!("  URL: {}", config.supabase.url);
-    println!("  RPC Endpoint: {}", config.supabase.rpc_endpoint);
-    if show_secrets {
-        println!("  Anon Key: {}", config.supabase.anon_key);
-    } else {
-        println!("  Anon Key: [REDACTED]");
+    for sink in &config.sinks {
+        println!("Sink '{}':", sink.name);
+        println!("  Type: {:?}", sink.sink_type);
+        match sink.sink_type {
+            config::SinkType::Supabase => {
+                if let Some(supabase) = &sink.supabase {
+                    println!("  URL: {}", supabase.url);
+                    println!("  RPC Endpoint: {}", supabase.rpc_endpoint);
+                    println!("  Send timestamp: {}", supabase.send_timestamp);
+                    println!("  Anon Key: {}", redact(&supabase.anon_key, show_secrets));
+                }
+            }
+            config::SinkType::Http => {
+                if let Some(http) = &sink.http {
+                    println!("  URL: {}", http.url);
+                    println!("  Method: {}", http.method);
+                    println!(
+                        "  Auth: {}",
+                        match &http.auth {
+                            Some(config::HttpAuthConfig::Basic { .. }) => "basic",
+                            Some(config::HttpAuthConfig::Bearer { .. }) => "bearer",
+                            Some(config::HttpAuthConfig::OAuth2 { .. }) => "oauth2",
+                            None => "none",
+                        }
+                    );
+                }
+            }
+        }
+        println!();
     }
-    println!();
 
-    println!("Source:");
-    println!("  Type: {:?}", config.source.source_type);
-    match config.source.source_type {
-        config::SourceType::Csv => {
-            if let Some(csv) = &config.source.csv {
-                println!("  Path: {}", csv.path.display());
-                println!("  Value Field: {}", csv.value_field);
-                println!("  Unit: {}", csv.unit);
+    for source in &config.sources {
+        println!("Source '{}':", source.name);
+        println!("  Type: {:?}", source.source_type);
+        println!(
+            "  API Key: {}",
+            redact(config.api_key_for(source), show_secrets)
+        );
+        println!(
+            "  Polling interval: {} seconds",
+            config.polling_interval_for(source)
+        );
+        match source.source_type {
+            config::SourceType::Csv => {
+                if let Some(csv) = &source.csv {
+                    println!("  Path: {}", csv.path.display());
+                    if let Some(pattern) = &csv.file_pattern {
+                        println!("  File Pattern: {}", pattern);
+                    }
+                    match &csv.expression {
+                        Some(expression) => println!("  Expression: {}", expression),
+                        None => println!("  Value Field: {}", csv.value_field),
+                    }
+                    println!("  Mode: {:?}", csv.mode);
+                    if let Some(field) = &csv.timestamp_field {
+                        println!("  Timestamp Field: {}", field);
+                    }
+                    if let Some(max_age) = csv.max_age_secs {
+                        println!("  Max Age: {} seconds", max_age);
+                    }
+                    println!("  Unit: {}", csv.unit);
+                }
+            }
+            config::SourceType::Json => {
+                if let Some(json) = &source.json {
+                    println!("  Path: {}", json.path.display());
+                    match &json.expression {
+                        Some(expression) => println!("  Expression: {}", expression),
+                        None => println!("  JSON Path: {}", json.json_path),
+                    }
+                    if let Some(path) = &json.timestamp_path {
+                        println!("  Timestamp Path: {}", path);
+                    }
+                    if let Some(max_age) = json.max_age_secs {
+                        println!("  Max Age: {} seconds", max_age);
+                    }
+                    println!("  Unit: {}", json.unit);
+                }
+            }
+            config::SourceType::Http => {
+                if let Some(http) = &source.http {
+                    println!("  URL: {}", http.url);
+                    println!("  Method: {}", http.method);
+                    println!("  Format: {:?}", http.format);
+                    match (&http.expression, http.format) {
+                        (Some(expression), _) => println!("  Expression: {}", expression),
+                        (None, config::HttpResponseFormat::Json) => {
+                            println!("  JSON Path: {}", http.json_path)
+                        }
+                        (None, config::HttpResponseFormat::Csv) => {
+                            println!("  Value Field: {}", http.value_field)
+                        }
+                        (None, config::HttpResponseFormat::Xml) => {
+                            println!(
+                                "  XML Path: {}",
+                                http.xml_path.as_deref().unwrap_or_default()
+                            )
+                        }
+                        (None, config::HttpResponseFormat::Regex) => {
+                            println!("  Pattern: {}", http.pattern.as_deref().unwrap_or_default())
+                        }
+                        (None, config::HttpResponseFormat::Number) => {}
+                    }
+                    if let Some(path) = &http.timestamp_path {
+                        println!("  Timestamp Path: {}", path);
+                    }
+                    if let Some(auth) = &http.auth {
+                        println!(
+                            "  Auth: {}",
+                            match auth {
+                                config::HttpAuthConfig::Basic { .. } => "basic",
+                                config::HttpAuthConfig::Bearer { .. } => "bearer",
+                                config::HttpAuthConfig::OAuth2 { .. } => "oauth2",
+                            }
+                        );
+                    }
+                    println!("  Unit: {}", http.unit);
+                }
+            }
+            config::SourceType::Modbus => {
+                if let Some(modbus) = &source.modbus {
+                    println!("  Host: {}:{}", modbus.host, modbus.port);
+                    println!("  Unit ID: {}", modbus.unit_id);
+                    println!(
+                        "  Register: {} ({:?}, {:?})",
+                        modbus.address, modbus.register_type, modbus.data_type
+                    );
+                    println!("  Unit: {}", modbus.unit);
+                }
+            }
+            config::SourceType::Mqtt => {
+                if let Some(mqtt) = &source.mqtt {
+                    println!("  Broker: {}:{}", mqtt.host, mqtt.port);
+                    println!("  Topic: {}", mqtt.topic);
+                    if let Some(json_path) = &mqtt.json_path {
+                        println!("  JSON Path: {}", json_path);
+                    }
+                    println!("  Unit: {}", mqtt.unit);
+                }
             }
         }
-        config::SourceType::Json => {
-            if let Some(json) = &config.source.json {
-                println!("  Path: {}", json.path.display());
-                println!("  JSON Path: {}", json.json_path);
-                println!("  Unit: {}", json.unit);
+        if let Some(counter) = &source.counter {
+            match counter.rollover {
+                Some(rollover) => println!("  Counter: rolls over at {}", rollover),
+                None => println!("  Counter: yes"),
             }
         }
-        config::SourceType::Http => {
-            if let Some(http) = &config.source.http {
-                println!("  URL: {}", http.url);
-                println!("  Method: {}", http.method);
-                println!("  JSON Path: {}", http.json_path);
-                println!("  Unit: {}", http.unit);
+        if let Some(filter) = &source.filter {
+            if filter.min.is_some() || filter.max.is_some() {
+                println!(
+                    "  Accepted Range: {} to {}",
+                    filter.min.map_or("-".to_string(), |v| v.to_string()),
+                    filter.max.map_or("-".to_string(), |v| v.to_string())
+                );
+            }
+            if let Some(rate) = filter.max_rate_per_sec {
+                println!("  Max Rate: {} per second", rate);
             }
+            if !filter.ignore_values.is_empty() {
+                println!("  Ignored Values: {:?}", filter.ignore_values);
+            }
+        }
+        if let Some(aggregate) = &source.aggregate {
+            println!(
+                "  Aggregate: {:?} over {} seconds",
+                aggregate.statistic, aggregate.window_secs
+            );
+        }
+        if let Some(retry) = &source.retry {
+            println!("  Read Attempts: {}", retry.max_attempts);
+        }
+        if !source.fallbacks.is_empty() {
+            println!("  Fallbacks: {}", source.fallbacks.join(", "));
         }
+        if let Some(input_unit) = source.input_unit {
+            println!(
+                "  Input Unit: {} (converted to {})",
+                input_unit,
+                source.unit().unwrap_or_default()
+            );
+        }
+        println!();
     }
-    println!();
 
     println!("Logging:");
     println!("  Level: {}", config.logging.level);
@@ -212,6 +354,14 @@ fn show_config(cli: Cli, show_secrets: bool) -> Result<(), AgentError> {
     Ok(())
 }
 
+fn redact(secret: &str, show_secrets: bool) -> String {
+    if show_secrets {
+        secret.to_string()
+    } else {
+        format!("{}...", secret.chars().take(12).collect::<String>())
+    }
+}
+
 fn validate_config(cli: Cli) -> Result<(), AgentError> {
     match AgentConfig::load(cli.config.as_deref()) {
         Ok(_) => {
@@ -249,5 +399,27 @@ async fn show_status() -> Result<(), AgentError> {
         println!("Service status not available on this platform");
     }
 
+    let status = match health::AgentStatus::load() {
+        Ok(status) => status,
+        Err(e) => {
+            println!();
+            println!("Cannot show disabled sources: {}", e);
+            println!("Run the command as the user the service runs as, e.g. with sudo.");
+            return Ok(());
+        }
+    };
+    if !status.disabled_sources.is_empty() {
+        println!();
+        println!("Disabled sources (fix the configuration to re-enable them):");
+        for (name, disabled) in &status.disabled_sources {
+            println!(
+                "  {} since {}: {}",
+                name,
+                disabled.disabled_at.to_rfc3339(),
+                disabled.error
+            );
+        }
+    }
+
     Ok(())
 }
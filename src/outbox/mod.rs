@@ -0,0 +1,300 @@
+use crate::error::OutboxError;
+use crate::sources::Reading;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// A reading waiting to be delivered, together with the POI it belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub api_key: String,
+    pub reading: Reading,
+}
+
+/// Store-and-forward queue sitting between the sources and the transport.
+///
+/// Entries are kept in memory and, when a path is set, mirrored to a JSON
+/// Lines file: new entries are appended, acknowledged ones are removed when
+/// the file is compacted by `sync`. Delivery is at-least-once.
+pub struct Outbox {
+    path: Option<PathBuf>,
+    entries: VecDeque<OutboxEntry>,
+    max_entries: usize,
+    max_age: Duration,
+    dirty: bool,
+}
+
+impl Outbox {
+    /// Open (or create) a persistent outbox, loading any backlog left from a previous run.
+    pub fn open(path: &Path, max_entries: usize, max_age_hours: u64) -> Result<Self, OutboxError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| OutboxError::OpenFailed(e.to_string()))?;
+        }
+
+        let mut entries = VecDeque::new();
+        if path.exists() {
+            let file = File::open(path).map_err(|e| OutboxError::OpenFailed(e.to_string()))?;
+            for (line_no, line) in BufReader::new(file).lines().enumerate() {
+                let line = line.map_err(|e| OutboxError::OpenFailed(e.to_string()))?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<OutboxEntry>(&line) {
+                    Ok(entry) => entries.push_back(entry),
+                    Err(e) => {
+                        // Most likely a line cut short by a crash mid-write
+                        warn!(
+                            path = %path.display(),
+                            line = line_no + 1,
+                            error = %e,
+                            "Skipping unreadable outbox entry"
+                        );
+                    }
+                }
+            }
+        }
+
+        let mut outbox = Self {
+            path: Some(path.to_path_buf()),
+            entries,
+            max_entries,
+            max_age: Duration::hours(max_age_hours as i64),
+            // Compact once on startup to drop any unreadable lines
+            dirty: true,
+        };
+        outbox.enforce_limits();
+        outbox.sync()?;
+        Ok(outbox)
+    }
+
+    /// Create an outbox that only lives in memory and is lost on restart.
+    pub fn in_memory(max_entries: usize, max_age_hours: u64) -> Self {
+        Self {
+            path: None,
+            entries: VecDeque::new(),
+            max_entries,
+            max_age: Duration::hours(max_age_hours as i64),
+            dirty: false,
+        }
+    }
+
+    /// Queue an entry. Returns how many of the oldest entries the limits discarded to make room.
+    pub fn push(&mut self, entry: OutboxEntry) -> Result<usize, OutboxError> {
+        if let Some(path) = &self.path {
+            append_line(path, &entry)?;
+        }
+        self.entries.push_back(entry);
+
+        let dropped = self.enforce_limits();
+        if dropped > 0 {
+            self.sync()?;
+        }
+        Ok(dropped)
+    }
+
+    pub fn front(&self) -> Option<&OutboxEntry> {
+        self.entries.front()
+    }
+
+    /// Acknowledge the oldest entry. The file is only rewritten on the next `sync`.
+    pub fn pop_front(&mut self) -> Option<OutboxEntry> {
+        let entry = self.entries.pop_front();
+        if entry.is_some() {
+            self.dirty = true;
+        }
+        entry
+    }
+
+    /// Copies of the `n` oldest entries.
+    pub fn front_n(&self, n: usize) -> Vec<OutboxEntry> {
+        self.entries.iter().take(n).cloned().collect()
+    }
+
+    /// Acknowledge some of the oldest entries: `acked[i]` refers to the i-th oldest.
+    /// Entries that are not acknowledged stay at the front, in order.
+    pub fn ack_front(&mut self, acked: &[bool]) {
+        let mut kept = Vec::new();
+        for &ack in acked {
+            match self.entries.pop_front() {
+                Some(entry) if !ack => kept.push(entry),
+                Some(_) => self.dirty = true,
+                None => break,
+            }
+        }
+        for entry in kept.into_iter().rev() {
+            self.entries.push_front(entry);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Rewrite the backing file so it matches the in-memory queue.
+    pub fn sync(&mut self) -> Result<(), OutboxError> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let Some(path) = &self.path else {
+            self.dirty = false;
+            return Ok(());
+        };
+
+        let tmp_path = path.with_extension("jsonl.tmp");
+        let file = File::create(&tmp_path).map_err(|e| OutboxError::WriteFailed(e.to_string()))?;
+        let mut writer = BufWriter::new(file);
+        for entry in &self.entries {
+            let line = serde_json::to_string(entry)
+                .map_err(|e| OutboxError::WriteFailed(e.to_string()))?;
+            writeln!(writer, "{}", line).map_err(|e| OutboxError::WriteFailed(e.to_string()))?;
+        }
+        writer
+            .into_inner()
+            .map_err(|e| OutboxError::WriteFailed(e.to_string()))?
+            .sync_all()
+            .map_err(|e| OutboxError::WriteFailed(e.to_string()))?;
+        fs::rename(&tmp_path, path).map_err(|e| OutboxError::WriteFailed(e.to_string()))?;
+
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Drop entries that are too old or exceed the size cap, oldest first.
+    fn enforce_limits(&mut self) -> usize {
+        let cutoff = Utc::now() - self.max_age;
+        let mut dropped = 0;
+
+        while self
+            .entries
+            .front()
+            .is_some_and(|e| e.reading.timestamp < cutoff)
+        {
+            self.entries.pop_front();
+            dropped += 1;
+        }
+
+        while self.entries.len() > self.max_entries {
+            self.entries.pop_front();
+            dropped += 1;
+        }
+
+        if dropped > 0 {
+            warn!(
+                dropped = dropped,
+                remaining = self.entries.len(),
+                "Outbox limits reached, discarding oldest readings"
+            );
+            self.dirty = true;
+        }
+
+        dropped
+    }
+}
+
+fn append_line(path: &Path, entry: &OutboxEntry) -> Result<(), OutboxError> {
+    let line = serde_json::to_string(entry).map_err(|e| OutboxError::WriteFailed(e.to_string()))?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| OutboxError::WriteFailed(e.to_string()))?;
+    writeln!(file, "{}", line).map_err(|e| OutboxError::WriteFailed(e.to_string()))?;
+    file.sync_data()
+        .map_err(|e| OutboxError::WriteFailed(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(value: f64) -> OutboxEntry {
+        OutboxEntry {
+            api_key: "poi-key".to_string(),
+            reading: Reading {
+                value,
+                unit: "kW".to_string(),
+                timestamp: Utc::now(),
+                source_id: "test".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_outbox_survives_reopen_in_order() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("outbox.jsonl");
+
+        let mut outbox = Outbox::open(&path, 100, 24).unwrap();
+        outbox.push(entry(1.0)).unwrap();
+        outbox.push(entry(2.0)).unwrap();
+        outbox.push(entry(3.0)).unwrap();
+        assert_eq!(outbox.pop_front().unwrap().reading.value, 1.0);
+        outbox.sync().unwrap();
+        drop(outbox);
+
+        let mut outbox = Outbox::open(&path, 100, 24).unwrap();
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox.pop_front().unwrap().reading.value, 2.0);
+        assert_eq!(outbox.pop_front().unwrap().reading.value, 3.0);
+    }
+
+    #[test]
+    fn test_outbox_partial_ack_keeps_order() {
+        let mut outbox = Outbox::in_memory(100, 24);
+        for value in [1.0, 2.0, 3.0, 4.0] {
+            outbox.push(entry(value)).unwrap();
+        }
+
+        let batch = outbox.front_n(3);
+        assert_eq!(batch.len(), 3);
+        outbox.ack_front(&[true, false, true]);
+
+        let remaining: Vec<f64> = outbox.front_n(10).iter().map(|e| e.reading.value).collect();
+        assert_eq!(remaining, vec![2.0, 4.0]);
+    }
+
+    #[test]
+    fn test_outbox_caps_size_and_age() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("outbox.jsonl");
+
+        let mut outbox = Outbox::open(&path, 2, 1).unwrap();
+        let mut old = entry(0.0);
+        old.reading.timestamp = Utc::now() - Duration::hours(2);
+        outbox.push(old).unwrap();
+        assert!(outbox.is_empty());
+
+        for value in [1.0, 2.0, 3.0] {
+            outbox.push(entry(value)).unwrap();
+        }
+        assert_eq!(outbox.len(), 2);
+        assert_eq!(outbox.front().unwrap().reading.value, 2.0);
+
+        let reopened = Outbox::open(&path, 2, 1).unwrap();
+        assert_eq!(reopened.len(), 2);
+    }
+
+    #[test]
+    fn test_outbox_skips_truncated_line() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("outbox.jsonl");
+
+        let mut outbox = Outbox::open(&path, 100, 24).unwrap();
+        outbox.push(entry(1.0)).unwrap();
+        drop(outbox);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"api_key\":\"po").unwrap();
+
+        let outbox = Outbox::open(&path, 100, 24).unwrap();
+        assert_eq!(outbox.len(), 1);
+    }
+}
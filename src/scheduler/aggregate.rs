@@ -0,0 +1,199 @@
+use crate::config::{AggregateConfig, Statistic};
+use crate::sources::Reading;
+use chrono::{DateTime, TimeZone, Utc};
+
+/// Folds a source's readings into one per clock-aligned window.
+///
+/// A window closes when a reading for a later window arrives, or once the
+/// clock is `grace_secs` past its end, so devices whose timestamps lag behind
+/// the agent's clock still get all their readings into one window.
+pub(super) struct Aggregator {
+    window_secs: i64,
+    grace_secs: i64,
+    statistic: Statistic,
+    current: Option<Window>,
+    /// End of the last window sent; readings before it come too late
+    emitted_until: Option<i64>,
+}
+
+struct Window {
+    start: i64,
+    count: u32,
+    sum: f64,
+    min: f64,
+    max: f64,
+    last: Reading,
+}
+
+impl Aggregator {
+    pub(super) fn new(config: &AggregateConfig) -> Self {
+        Self {
+            window_secs: config.window_secs as i64,
+            grace_secs: config.grace_secs.unwrap_or(config.window_secs) as i64,
+            statistic: config.statistic,
+            current: None,
+            emitted_until: None,
+        }
+    }
+
+    fn window_start(&self, reading: &Reading) -> i64 {
+        reading.timestamp.timestamp().div_euclid(self.window_secs) * self.window_secs
+    }
+
+    /// Whether the reading belongs to a window that has already been sent.
+    pub(super) fn is_late(&self, reading: &Reading) -> bool {
+        self.emitted_until
+            .is_some_and(|end| self.window_start(reading) < end)
+    }
+
+    /// Add a reading. Returns the previous window's result when this reading starts a new one.
+    ///
+    /// Readings for a window already sent are ignored, see `is_late`.
+    pub(super) fn push(&mut self, reading: Reading) -> Option<Reading> {
+        if self.is_late(&reading) {
+            return None;
+        }
+        let start = self.window_start(&reading);
+
+        match &mut self.current {
+            // Late readings count towards the open window rather than being lost
+            Some(window) if start <= window.start => {
+                window.count += 1;
+                window.sum += reading.value;
+                window.min = window.min.min(reading.value);
+                window.max = window.max.max(reading.value);
+                window.last = reading;
+                None
+            }
+            _ => {
+                let window = Window {
+                    start,
+                    count: 1,
+                    sum: reading.value,
+                    min: reading.value,
+                    max: reading.value,
+                    last: reading,
+                };
+                let finished = self.current.replace(window);
+                finished.map(|w| self.finish(w))
+            }
+        }
+    }
+
+    /// The open window's result once `now` is past its end and the grace period,
+    /// so a window is still sent when the source stops producing readings.
+    pub(super) fn flush(&mut self, now: DateTime<Utc>) -> Option<Reading> {
+        let ended = self
+            .current
+            .as_ref()
+            .is_some_and(|w| now.timestamp() >= w.start + self.window_secs + self.grace_secs);
+        if ended {
+            self.current.take().map(|w| self.finish(w))
+        } else {
+            None
+        }
+    }
+
+    /// The window's statistic, timestamped at the end of the window.
+    fn finish(&mut self, window: Window) -> Reading {
+        self.emitted_until = Some(window.start + self.window_secs);
+        let value = match self.statistic {
+            Statistic::Mean => window.sum / window.count as f64,
+            Statistic::Min => window.min,
+            Statistic::Max => window.max,
+            Statistic::Last => window.last.value,
+        };
+
+        Reading {
+            value,
+            timestamp: Utc
+                .timestamp_opt(window.start + self.window_secs, 0)
+                .single()
+                .unwrap_or(window.last.timestamp),
+            ..window.last
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(secs, 0).unwrap()
+    }
+
+    fn reading(value: f64, secs: i64) -> Reading {
+        Reading {
+            value,
+            unit: "kW".to_string(),
+            timestamp: at(secs),
+            source_id: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_windows_align_to_the_clock() {
+        let mut mean = Aggregator::new(&AggregateConfig {
+            window_secs: 60,
+            statistic: Statistic::Mean,
+            grace_secs: Some(0),
+        });
+
+        // Starting mid-window still closes at the next minute
+        assert!(mean.push(reading(10.0, 6030)).is_none());
+        assert!(mean.push(reading(20.0, 6045)).is_none());
+        assert!(mean.flush(at(6059)).is_none());
+
+        let done = mean.push(reading(40.0, 6060)).unwrap();
+        assert_eq!(done.value, 15.0);
+        assert_eq!(done.timestamp, at(6060));
+        assert_eq!(done.unit, "kW");
+
+        // No reading after the window: the clock closes it
+        let done = mean.flush(at(6125)).unwrap();
+        assert_eq!(done.value, 40.0);
+        assert_eq!(done.timestamp, at(6120));
+        assert!(mean.flush(at(6200)).is_none());
+
+        let mut max = Aggregator::new(&AggregateConfig {
+            window_secs: 300,
+            statistic: Statistic::Max,
+            grace_secs: Some(0),
+        });
+        max.push(reading(3.0, 0));
+        max.push(reading(7.0, 100));
+        max.push(reading(5.0, 200));
+        assert_eq!(max.flush(at(300)).unwrap().value, 7.0);
+    }
+
+    #[test]
+    fn test_lagging_timestamps_fill_one_window() {
+        let mut mean = Aggregator::new(&AggregateConfig {
+            window_secs: 60,
+            statistic: Statistic::Mean,
+            grace_secs: Some(300),
+        });
+
+        // The device clock runs three minutes behind the agent's
+        let lag = 180;
+        for (value, secs) in [(10.0, 6000), (20.0, 6020), (30.0, 6040)] {
+            assert!(mean.push(reading(value, secs)).is_none());
+            assert!(mean.flush(at(secs + lag)).is_none());
+        }
+        let done = mean.push(reading(40.0, 6060)).unwrap();
+        assert_eq!(done.value, 20.0);
+        assert_eq!(done.timestamp, at(6060));
+
+        // A reading for the window just sent doesn't open it again
+        let straggler = reading(99.0, 6050);
+        assert!(mean.is_late(&straggler));
+        assert!(mean.push(straggler).is_none());
+
+        // Only the clock is left to close the last window, once the grace period is over
+        assert!(mean.flush(at(6120 + 299)).is_none());
+        let done = mean.flush(at(6120 + 300)).unwrap();
+        assert_eq!(done.value, 40.0);
+        assert_eq!(done.timestamp, at(6120));
+    }
+}
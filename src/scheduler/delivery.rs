@@ -0,0 +1,408 @@
+use crate::config::{AgentConfig, OutboxSettings, RetrySettings, SinkConfig};
+use crate::error::{AgentError, OutboxError, Retriable, TransportError};
+use crate::health::Metrics;
+use crate::outbox::{Outbox, OutboxEntry};
+use crate::transport::{create_transport, with_retry, Transport};
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+/// How many acknowledged readings to replay before compacting the outbox file
+const OUTBOX_SYNC_EVERY: usize = 50;
+
+/// Delivers readings to one sink, queueing them in that sink's own outbox so
+/// a slow or unreachable destination doesn't hold back the others.
+pub(super) struct SinkWorker {
+    name: String,
+    transport: Arc<dyn Transport>,
+    outbox: Outbox,
+    retry: RetrySettings,
+    metrics: Arc<Metrics>,
+}
+
+impl SinkWorker {
+    pub(super) fn new(
+        sink: &SinkConfig,
+        config: &AgentConfig,
+        metrics: Arc<Metrics>,
+    ) -> Result<Self, AgentError> {
+        let transport = create_transport(sink)?;
+        let outbox = open_outbox(&config.outbox, &sink.name)?;
+
+        if !outbox.is_empty() {
+            info!(
+                sink = %sink.name,
+                pending = outbox.len(),
+                "Loaded unsent readings from outbox"
+            );
+        }
+        metrics.set_pending(&sink.name, outbox.len());
+
+        Ok(Self {
+            name: sink.name.clone(),
+            transport: Arc::from(transport),
+            outbox,
+            retry: config.retry.clone(),
+            metrics,
+        })
+    }
+
+    /// Queue and send readings until the sender side is dropped.
+    pub(super) async fn run(
+        mut self,
+        mut entries_rx: mpsc::UnboundedReceiver<OutboxEntry>,
+        flush_every: Duration,
+    ) {
+        // Retry the backlog even when no source produces new readings
+        let mut flush_interval = tokio::time::interval(flush_every);
+        flush_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                entry = entries_rx.recv() => match entry {
+                    Some(entry) => {
+                        // Everything already waiting goes out in the same flush
+                        self.queue(entry);
+                        while let Ok(entry) = entries_rx.try_recv() {
+                            self.queue(entry);
+                        }
+                        self.flush_outbox(&mut entries_rx).await;
+                    }
+                    None => break,
+                },
+                _ = flush_interval.tick() => {
+                    if !self.outbox.is_empty() {
+                        self.flush_outbox(&mut entries_rx).await;
+                    }
+                }
+            }
+        }
+
+        self.sync_outbox();
+    }
+
+    /// Store a reading in the outbox. Returns how many of the oldest readings were discarded to make room.
+    fn queue(&mut self, entry: OutboxEntry) -> usize {
+        match self.outbox.push(entry) {
+            Ok(discarded) => discarded,
+            Err(e) => {
+                error!(sink = %self.name, error = %e, "Failed to store reading in outbox");
+                0
+            }
+        }
+    }
+
+    /// Send queued readings oldest first, stopping at the first one that cannot be delivered.
+    async fn flush_outbox(&mut self, entries_rx: &mut mpsc::UnboundedReceiver<OutboxEntry>) {
+        match self.transport.batch_size() {
+            Some(batch_size) => self.flush_outbox_batched(entries_rx, batch_size).await,
+            None => {
+                self.flush_outbox_single(entries_rx, usize::MAX).await;
+            }
+        }
+
+        self.sync_outbox();
+        self.metrics.set_pending(&self.name, self.outbox.len());
+    }
+
+    /// `with_retry`, counting the extra attempts in the metrics.
+    ///
+    /// Readings arriving meanwhile are stored in the outbox rather than left in
+    /// the channel, so a sink stuck retrying keeps them on disk and within
+    /// `max_entries`. Also returns how many of the oldest queued readings the
+    /// outbox limits discarded while the operation ran.
+    async fn with_retry<T, F, Fut>(
+        &mut self,
+        entries_rx: &mut mpsc::UnboundedReceiver<OutboxEntry>,
+        operation: F,
+    ) -> (Result<T, TransportError>, usize)
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, TransportError>>,
+    {
+        let attempts = AtomicU64::new(0);
+        let retry = self.retry.clone();
+        let send = with_retry(&retry, || {
+            attempts.fetch_add(1, Ordering::Relaxed);
+            operation()
+        });
+        tokio::pin!(send);
+
+        let mut discarded = 0;
+        let result = loop {
+            tokio::select! {
+                result = &mut send => break result,
+                Some(entry) = entries_rx.recv() => discarded += self.queue(entry),
+            }
+        };
+
+        self.metrics.record_retries(
+            &self.name,
+            attempts.load(Ordering::Relaxed).saturating_sub(1),
+        );
+        (result, discarded)
+    }
+
+    /// Send up to `limit` readings one request at a time. Returns false when delivery stalled.
+    async fn flush_outbox_single(
+        &mut self,
+        entries_rx: &mut mpsc::UnboundedReceiver<OutboxEntry>,
+        limit: usize,
+    ) -> bool {
+        let mut acknowledged = 0;
+
+        while let Some(entry) = self.outbox.front().cloned() {
+            if acknowledged >= limit {
+                break;
+            }
+
+            let transport = self.transport.clone();
+            let (result, discarded) = self.with_retry(entries_rx, || transport.send(&entry)).await;
+            let value = entry.reading.value;
+
+            let acked = match &result {
+                Ok(()) => true,
+                Err(e) => is_undeliverable(e),
+            };
+            // The outbox limits may have discarded the reading while it was being sent
+            if acked && discarded == 0 {
+                self.outbox.pop_front();
+            }
+
+            match result {
+                Ok(()) => {
+                    info!(
+                        sink = %self.name,
+                        value = value,
+                        unit = %entry.reading.unit,
+                        pending = self.outbox.len(),
+                        "Data sent successfully"
+                    );
+                    self.metrics.record_sent(&self.name, 1);
+                }
+                Err(e) if is_undeliverable(&e) => {
+                    error!(
+                        sink = %self.name,
+                        error = %e,
+                        value = value,
+                        "Sink rejected reading, dropping it from the outbox"
+                    );
+                    self.metrics.record_send_failure(&self.name);
+                }
+                Err(e) => {
+                    error!(
+                        sink = %self.name,
+                        error = %e,
+                        value = value,
+                        pending = self.outbox.len(),
+                        "Failed to send data after retries, keeping readings in outbox"
+                    );
+                    self.metrics.record_send_failure(&self.name);
+                    return false;
+                }
+            }
+
+            acknowledged += 1;
+            if acknowledged % OUTBOX_SYNC_EVERY == 0 {
+                self.sync_outbox();
+            }
+        }
+
+        true
+    }
+
+    /// Send readings `batch_size` at a time, acknowledging each reading from its own result.
+    async fn flush_outbox_batched(
+        &mut self,
+        entries_rx: &mut mpsc::UnboundedReceiver<OutboxEntry>,
+        batch_size: usize,
+    ) {
+        loop {
+            let batch = self.outbox.front_n(batch_size);
+            if batch.is_empty() {
+                return;
+            }
+
+            let transport = self.transport.clone();
+            let (result, discarded) = self
+                .with_retry(entries_rx, || transport.send_batch(&batch))
+                .await;
+
+            match result {
+                Ok(results) => {
+                    let mut stalled = false;
+                    let acked: Vec<bool> = results
+                        .iter()
+                        .zip(&batch)
+                        .map(|(result, entry)| match result {
+                            Ok(()) => true,
+                            Err(e) if is_undeliverable(e) => {
+                                error!(
+                                    sink = %self.name,
+                                    error = %e,
+                                    value = entry.reading.value,
+                                    "Sink rejected reading, dropping it from the outbox"
+                                );
+                                true
+                            }
+                            Err(e) => {
+                                warn!(
+                                    sink = %self.name,
+                                    error = %e,
+                                    value = entry.reading.value,
+                                    "Reading not accepted, keeping it in outbox"
+                                );
+                                stalled = true;
+                                false
+                            }
+                        })
+                        .collect();
+
+                    // Readings the outbox limits discarded during the send are gone already
+                    self.outbox.ack_front(&acked[discarded.min(acked.len())..]);
+                    let delivered = results.iter().filter(|r| r.is_ok()).count();
+                    if delivered > 0 {
+                        self.metrics.record_sent(&self.name, delivered as u64);
+                    }
+                    for _ in delivered..results.len() {
+                        self.metrics.record_send_failure(&self.name);
+                    }
+                    info!(
+                        sink = %self.name,
+                        sent = acked.iter().filter(|&&a| a).count(),
+                        pending = self.outbox.len(),
+                        "Batch sent"
+                    );
+                    self.sync_outbox();
+
+                    if stalled {
+                        return;
+                    }
+                }
+                Err(e) if is_undeliverable(&e) => {
+                    // One bad reading fails the whole batch, find it by sending them separately
+                    warn!(
+                        sink = %self.name,
+                        error = %e,
+                        readings = batch.len(),
+                        "Sink rejected batch, sending its readings one by one"
+                    );
+                    if !self.flush_outbox_single(entries_rx, batch.len()).await {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    error!(
+                        sink = %self.name,
+                        error = %e,
+                        pending = self.outbox.len(),
+                        "Failed to send batch after retries, keeping readings in outbox"
+                    );
+                    self.metrics.record_send_failure(&self.name);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn sync_outbox(&mut self) {
+        if let Err(e) = self.outbox.sync() {
+            error!(sink = %self.name, error = %e, "Failed to save outbox");
+        }
+    }
+}
+
+/// Errors that will fail the same way on every replay, so keeping the reading would block the queue.
+/// Authentication failures are excluded: they are fixed by configuration, not by the reading.
+fn is_undeliverable(error: &TransportError) -> bool {
+    !error.is_retriable() && !matches!(error, TransportError::AuthFailed(_))
+}
+
+fn open_outbox(settings: &OutboxSettings, sink_name: &str) -> Result<Outbox, OutboxError> {
+    if !settings.enabled {
+        return Ok(Outbox::in_memory(
+            settings.max_entries,
+            settings.max_age_hours,
+        ));
+    }
+
+    let dir = settings
+        .directory
+        .clone()
+        .or_else(|| AgentConfig::default_data_dir().map(|p| p.join("outbox")))
+        .unwrap_or_else(|| PathBuf::from("outbox"));
+
+    Outbox::open(
+        &dir.join(format!("{}.jsonl", sink_name)),
+        settings.max_entries,
+        settings.max_age_hours,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sources::Reading;
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use tempfile::TempDir;
+
+    /// A sink that is down for good.
+    struct Unreachable;
+
+    #[async_trait]
+    impl Transport for Unreachable {
+        async fn send(&self, _entry: &OutboxEntry) -> Result<(), TransportError> {
+            Err(TransportError::Network("Connection refused".to_string()))
+        }
+    }
+
+    fn entry(value: f64) -> OutboxEntry {
+        OutboxEntry {
+            api_key: "poi-key".to_string(),
+            reading: Reading {
+                value,
+                unit: "kW".to_string(),
+                timestamp: Utc::now(),
+                source_id: "test".to_string(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_readings_reach_the_outbox_while_retrying() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("sink.jsonl");
+        let worker = SinkWorker {
+            name: "sink".to_string(),
+            transport: Arc::new(Unreachable),
+            outbox: Outbox::open(&path, 3, 24).unwrap(),
+            // Keeps retrying the first reading for the whole test
+            retry: RetrySettings {
+                max_attempts: 10_000,
+                initial_delay_ms: 10,
+                max_delay_ms: 10,
+                multiplier: 1.0,
+            },
+            metrics: Arc::new(Metrics::new()),
+        };
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        let handle = tokio::spawn(worker.run(rx, Duration::from_secs(60)));
+        for value in 1..=5 {
+            tx.send(entry(value as f64)).unwrap();
+            tokio::time::sleep(Duration::from_millis(30)).await;
+        }
+        handle.abort();
+        let _ = handle.await;
+
+        // On disk and capped by max_entries, although the worker never left its first send
+        let outbox = Outbox::open(&path, 100, 24).unwrap();
+        let values: Vec<f64> = outbox.front_n(10).iter().map(|e| e.reading.value).collect();
+        assert_eq!(values, vec![3.0, 4.0, 5.0]);
+    }
+}
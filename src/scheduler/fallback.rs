@@ -0,0 +1,339 @@
+use crate::config::{AgentConfig, RetrySettings, SourceConfig};
+use crate::error::{AgentError, SourceError};
+use crate::health::Metrics;
+use crate::sources::{create_source, DataSource, Reading};
+use crate::transport::with_retry;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Polls in a row a permanent error must fail before the source is disabled,
+/// so a half-written file isn't mistaken for a missing column
+const PERMANENT_FAILURES_TO_DISABLE: u32 = 3;
+
+/// A source read with its retries, and the fallbacks read in order when it fails.
+pub(super) struct SourceChain {
+    links: Vec<Link>,
+    /// The link the last readings came from
+    served: Option<usize>,
+}
+
+struct Link {
+    name: String,
+    source: Box<dyn DataSource>,
+    /// Read once when unset
+    retry: Option<RetrySettings>,
+    permanent_failures: u32,
+    disabled: bool,
+}
+
+impl SourceChain {
+    pub(super) fn new(
+        config: &AgentConfig,
+        primary: &SourceConfig,
+        interval: Duration,
+    ) -> Result<Self, AgentError> {
+        let fallbacks = primary
+            .fallbacks
+            .iter()
+            .filter_map(|name| config.sources.iter().find(|s| s.name == *name));
+
+        let links = std::iter::once(primary)
+            .chain(fallbacks)
+            .map(|source_config| {
+                Ok(Link {
+                    name: source_config.name.clone(),
+                    source: create_source(source_config, interval)?,
+                    retry: source_config.retry.clone(),
+                    permanent_failures: 0,
+                    disabled: false,
+                })
+            })
+            .collect::<Result<Vec<_>, AgentError>>()?;
+
+        Ok(Self {
+            links,
+            served: None,
+        })
+    }
+
+    pub(super) fn source_id(&self) -> &str {
+        self.links[0].source.source_id()
+    }
+
+    /// Whether every source in the chain has been disabled.
+    pub(super) fn is_disabled(&self) -> bool {
+        self.links.iter().all(|link| link.disabled)
+    }
+
+    pub(super) fn disabled_count(&self) -> usize {
+        self.links.iter().filter(|link| link.disabled).count()
+    }
+
+    /// Readings from the first source that can be read, or the last source's error.
+    ///
+    /// A source failing with the same kind of permanent error poll after poll is
+    /// disabled and skipped from then on.
+    pub(super) async fn read_values(
+        &mut self,
+        metrics: &Metrics,
+    ) -> Result<Vec<Reading>, SourceError> {
+        let primary = self.links[0].name.clone();
+        let mut error = None;
+        self.served = None;
+
+        for i in 0..self.links.len() {
+            let link = &mut self.links[i];
+            if link.disabled {
+                continue;
+            }
+
+            let result = match &link.retry {
+                Some(retry) => with_retry(retry, || link.source.read_values()).await,
+                None => link.source.read_values().await,
+            };
+            match result {
+                Ok(readings) => {
+                    link.permanent_failures = 0;
+                    self.served = Some(i);
+                    if i > 0 {
+                        info!(source = %primary, fallback = %link.name, "Read from fallback source");
+                    }
+                    return Ok(readings);
+                }
+                Err(e) => {
+                    if e.is_permanent() {
+                        link.permanent_failures += 1;
+                    } else {
+                        link.permanent_failures = 0;
+                    }
+                    if link.permanent_failures >= PERMANENT_FAILURES_TO_DISABLE {
+                        link.disabled = true;
+                        error!(
+                            error = %e,
+                            source = %link.name,
+                            failures = link.permanent_failures,
+                            "Disabling source after a permanent error, fix the configuration to re-enable it"
+                        );
+                        metrics.record_disabled(&link.name, &e.to_string());
+                    }
+
+                    if let Some(next) = self.links[i + 1..].iter().find(|l| !l.disabled) {
+                        warn!(
+                            error = %e,
+                            source = %self.links[i].name,
+                            fallback = %next.name,
+                            "Failed to read source, trying fallback"
+                        );
+                    }
+                    error = Some(e);
+                }
+            }
+        }
+
+        Err(error
+            .unwrap_or_else(|| SourceError::ReadError(format!("Source {} is disabled", primary))))
+    }
+
+    /// `DataSource::commit` on the source the last readings came from.
+    pub(super) async fn commit(&self) -> Result<(), SourceError> {
+        match self.served {
+            Some(i) => self.links[i].source.commit().await,
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use chrono::Utc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// Fails its first `failures` reads with `error`.
+    struct Flaky {
+        failures: u32,
+        error: fn() -> SourceError,
+        reads: Arc<AtomicU32>,
+        value: f64,
+    }
+
+    #[async_trait]
+    impl DataSource for Flaky {
+        async fn read_value(&self) -> Result<Reading, SourceError> {
+            if self.reads.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err((self.error)());
+            }
+            Ok(Reading {
+                value: self.value,
+                unit: "kW".to_string(),
+                timestamp: Utc::now(),
+                source_id: "flaky".to_string(),
+            })
+        }
+
+        fn source_id(&self) -> &str {
+            "flaky"
+        }
+    }
+
+    fn link(name: &str, failures: u32, attempts: u32, value: f64) -> (Link, Arc<AtomicU32>) {
+        let reads = Arc::new(AtomicU32::new(0));
+        let link = Link {
+            name: name.to_string(),
+            source: Box::new(Flaky {
+                failures,
+                error: || SourceError::ReadError("partially written".to_string()),
+                reads: reads.clone(),
+                value,
+            }),
+            retry: Some(RetrySettings {
+                max_attempts: attempts,
+                initial_delay_ms: 1,
+                max_delay_ms: 1,
+                multiplier: 1.0,
+            }),
+            permanent_failures: 0,
+            disabled: false,
+        };
+        (link, reads)
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_fallbacks() {
+        let metrics = Metrics::new();
+
+        // Two failures are covered by three attempts
+        let (primary, primary_reads) = link("local", 2, 3, 1.0);
+        let (cloud, cloud_reads) = link("cloud", 0, 1, 2.0);
+        let mut chain = SourceChain {
+            links: vec![primary, cloud],
+            served: None,
+        };
+        assert_eq!(chain.read_values(&metrics).await.unwrap()[0].value, 1.0);
+        assert_eq!(primary_reads.load(Ordering::SeqCst), 3);
+        assert_eq!(cloud_reads.load(Ordering::SeqCst), 0);
+
+        // Out of attempts: the fallback answers
+        let (primary, _) = link("local", 5, 2, 1.0);
+        let (broken, broken_reads) = link("modem", 5, 1, 3.0);
+        let (cloud, _) = link("cloud", 0, 1, 2.0);
+        let mut chain = SourceChain {
+            links: vec![primary, broken, cloud],
+            served: None,
+        };
+        assert_eq!(chain.read_values(&metrics).await.unwrap()[0].value, 2.0);
+        assert_eq!(broken_reads.load(Ordering::SeqCst), 1);
+
+        let (primary, _) = link("local", 5, 1, 1.0);
+        let mut chain = SourceChain {
+            links: vec![primary],
+            served: None,
+        };
+        assert!(chain.read_values(&metrics).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_disable_a_source() {
+        let metrics = Metrics::new();
+        let reads = Arc::new(AtomicU32::new(0));
+        let primary = Link {
+            name: "local".to_string(),
+            source: Box::new(Flaky {
+                failures: u32::MAX,
+                error: || SourceError::ColumnNotFound("Column 'P' not found".to_string()),
+                reads: reads.clone(),
+                value: 1.0,
+            }),
+            // Retrying can't find a missing column
+            retry: Some(RetrySettings {
+                max_attempts: 5,
+                initial_delay_ms: 1,
+                max_delay_ms: 1,
+                multiplier: 1.0,
+            }),
+            permanent_failures: 0,
+            disabled: false,
+        };
+        let (cloud, _) = link("cloud", 0, 1, 2.0);
+        let mut chain = SourceChain {
+            links: vec![primary, cloud],
+            served: None,
+        };
+
+        for _ in 0..PERMANENT_FAILURES_TO_DISABLE {
+            assert_eq!(chain.read_values(&metrics).await.unwrap()[0].value, 2.0);
+        }
+        assert_eq!(reads.load(Ordering::SeqCst), PERMANENT_FAILURES_TO_DISABLE);
+        assert_eq!(chain.disabled_count(), 1);
+        assert!(metrics.status().disabled_sources.contains_key("local"));
+
+        // Only the fallback is read from now on
+        chain.read_values(&metrics).await.unwrap();
+        assert_eq!(reads.load(Ordering::SeqCst), PERMANENT_FAILURES_TO_DISABLE);
+        assert!(!chain.is_disabled());
+    }
+
+    #[tokio::test]
+    async fn test_missing_values_keep_a_source_enabled() {
+        let metrics = Metrics::new();
+        let reads = Arc::new(AtomicU32::new(0));
+        // An API answering with an empty array at night
+        let primary = Link {
+            name: "cloud".to_string(),
+            source: Box::new(Flaky {
+                failures: 2 * PERMANENT_FAILURES_TO_DISABLE,
+                error: || SourceError::ValueNotFound("No value found at path: $[0].P".to_string()),
+                reads: reads.clone(),
+                value: 1.0,
+            }),
+            retry: None,
+            permanent_failures: 0,
+            disabled: false,
+        };
+        let mut chain = SourceChain {
+            links: vec![primary],
+            served: None,
+        };
+
+        for _ in 0..2 * PERMANENT_FAILURES_TO_DISABLE {
+            assert!(chain.read_values(&metrics).await.is_err());
+        }
+        assert_eq!(chain.disabled_count(), 0);
+        assert!(metrics.status().disabled_sources.is_empty());
+
+        // Values are back in the morning
+        assert_eq!(chain.read_values(&metrics).await.unwrap()[0].value, 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_values_are_not_retried() {
+        let metrics = Metrics::new();
+        let reads = Arc::new(AtomicU32::new(0));
+        let primary = Link {
+            name: "local".to_string(),
+            source: Box::new(Flaky {
+                failures: 1,
+                error: || SourceError::InvalidValueType("'n/a'".to_string()),
+                reads: reads.clone(),
+                value: 1.0,
+            }),
+            retry: Some(RetrySettings {
+                max_attempts: 5,
+                initial_delay_ms: 1,
+                max_delay_ms: 1,
+                multiplier: 1.0,
+            }),
+            permanent_failures: 0,
+            disabled: false,
+        };
+        let mut chain = SourceChain {
+            links: vec![primary],
+            served: None,
+        };
+
+        assert!(chain.read_values(&metrics).await.is_err());
+        assert_eq!(reads.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,14 +1,46 @@
+mod aggregate;
+mod delivery;
+mod fallback;
+
 use crate::config::AgentConfig;
 use crate::error::AgentError;
-use crate::sources::{create_source, DataSource};
-use crate::transport::{with_retry, SupabaseClient};
-use std::time::Duration;
-use tokio::sync::broadcast;
-use tracing::{error, info, warn};
+use crate::health::{self, Metrics};
+use crate::outbox::OutboxEntry;
+use crate::sources::ValueFilter;
+use aggregate::Aggregator;
+use chrono::Utc;
+use delivery::SinkWorker;
+use fallback::SourceChain;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+/// Readings buffered between the source tasks and the fan-out to sinks
+const READINGS_CHANNEL_CAPACITY: usize = 256;
+
+/// How long sinks get to finish an in-flight send once shutdown is requested
+const SINK_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often the configuration file is checked for changes
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A source together with the settings it is polled with.
+struct SourceTask {
+    name: String,
+    api_key: String,
+    interval: Duration,
+    source: SourceChain,
+    filter: ValueFilter,
+    aggregator: Option<Aggregator>,
+}
 
 pub struct Scheduler {
-    source: Box<dyn DataSource>,
-    transport: SupabaseClient,
+    sources: Vec<SourceTask>,
+    sinks: Vec<SinkWorker>,
+    metrics: Arc<Metrics>,
     config: AgentConfig,
     shutdown_rx: broadcast::Receiver<()>,
 }
@@ -17,13 +49,40 @@ impl Scheduler {
     pub fn new(
         config: AgentConfig,
         shutdown_rx: broadcast::Receiver<()>,
+        metrics: Arc<Metrics>,
     ) -> Result<Self, AgentError> {
-        let source = create_source(&config.source)?;
-        let transport = SupabaseClient::new(&config.supabase)?;
+        // Fallbacks are only read when the source naming them fails
+        let fallbacks: Vec<&str> = config
+            .sources
+            .iter()
+            .flat_map(|s| s.fallbacks.iter().map(String::as_str))
+            .collect();
+        let sources = config
+            .sources
+            .iter()
+            .filter(|source_config| !fallbacks.contains(&source_config.name.as_str()))
+            .map(|source_config| {
+                let interval = Duration::from_secs(config.polling_interval_for(source_config));
+                Ok(SourceTask {
+                    name: source_config.name.clone(),
+                    api_key: config.api_key_for(source_config).to_string(),
+                    interval,
+                    source: SourceChain::new(&config, source_config, interval)?,
+                    filter: ValueFilter::new(source_config.filter.as_ref()),
+                    aggregator: source_config.aggregate.as_ref().map(Aggregator::new),
+                })
+            })
+            .collect::<Result<Vec<_>, AgentError>>()?;
+        let sinks = config
+            .sinks
+            .iter()
+            .map(|sink_config| SinkWorker::new(sink_config, &config, metrics.clone()))
+            .collect::<Result<Vec<_>, AgentError>>()?;
 
         Ok(Self {
-            source,
-            transport,
+            sources,
+            sinks,
+            metrics,
             config,
             shutdown_rx,
         })
@@ -32,103 +91,210 @@ impl Scheduler {
     pub async fn run(&mut self) -> Result<(), AgentError> {
         info!(
             instance_id = %self.config.agent.instance_id,
-            interval_secs = self.config.agent.polling_interval_secs,
-            source = self.source.source_id(),
+            sources = self.sources.len(),
+            sinks = self.sinks.len(),
             "Starting scheduler"
         );
 
-        let mut interval = tokio::time::interval(Duration::from_secs(
-            self.config.agent.polling_interval_secs,
-        ));
+        // Every source starts enabled again, so a fixed configuration takes effect
+        self.metrics.clear_disabled();
+        write_status(&self.metrics);
+
+        let (readings_tx, mut readings_rx) = mpsc::channel(READINGS_CHANNEL_CAPACITY);
+        let tasks: Vec<_> = self
+            .sources
+            .drain(..)
+            .map(|task| tokio::spawn(poll_source(task, readings_tx.clone(), self.metrics.clone())))
+            .collect();
+        drop(readings_tx);
 
-        // Don't burst on startup
-        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        // Unbounded so a sink stuck retrying never blocks the others; the
+        // worker moves readings into its outbox even while it retries.
+        let flush_every = Duration::from_secs(self.config.agent.polling_interval_secs);
+        let (sink_txs, workers): (Vec<_>, Vec<_>) = self
+            .sinks
+            .drain(..)
+            .map(|worker| {
+                let (tx, rx) = mpsc::unbounded_channel();
+                (tx, tokio::spawn(worker.run(rx, flush_every)))
+            })
+            .unzip();
 
         loop {
             tokio::select! {
-                _ = interval.tick() => {
-                    self.poll_and_send().await;
+                Some(entry) = readings_rx.recv() => {
+                    for tx in &sink_txs {
+                        let _ = tx.send(entry.clone());
+                    }
                 }
                 _ = self.shutdown_rx.recv() => {
-                    info!("Shutdown signal received, stopping scheduler");
+                    info!("Stopping scheduler");
                     break;
                 }
             }
         }
 
+        for task in tasks {
+            task.abort();
+        }
+
+        // Readings already taken from their source must still reach the outboxes
+        while let Ok(entry) = readings_rx.try_recv() {
+            for tx in &sink_txs {
+                let _ = tx.send(entry.clone());
+            }
+        }
+
+        // Closing the channels lets each worker save its outbox and stop
+        drop(sink_txs);
+        for mut worker in workers {
+            if tokio::time::timeout(SINK_SHUTDOWN_TIMEOUT, &mut worker)
+                .await
+                .is_err()
+            {
+                warn!("Sink did not stop in time, unsent readings stay in its outbox");
+                worker.abort();
+            }
+        }
+
         Ok(())
     }
+}
 
-    async fn poll_and_send(&self) {
-        info!(source = self.source.source_id(), "Polling data source");
+/// Poll one source on its own interval and hand readings over to the scheduler.
+async fn poll_source(
+    mut task: SourceTask,
+    readings_tx: mpsc::Sender<OutboxEntry>,
+    metrics: Arc<Metrics>,
+) {
+    info!(
+        source = %task.name,
+        source_id = task.source.source_id(),
+        interval_secs = task.interval.as_secs(),
+        "Starting source"
+    );
 
-        match self.source.read_value().await {
-            Ok(reading) => {
-                info!(
-                    value = reading.value,
-                    unit = %reading.unit,
-                    source = %reading.source_id,
-                    "Read value from source"
-                );
+    let mut interval = tokio::time::interval(task.interval);
 
-                let api_key = self.config.poi.api_key.clone();
-                let transport = &self.transport;
-                let retry_settings = &self.config.retry;
-                let value = reading.value;
-                let unit = reading.unit.clone();
+    // Don't burst on startup
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
-                let result = with_retry(retry_settings, || {
-                    let api_key = api_key.clone();
-                    let unit = unit.clone();
-                    async move { transport.insert_live_data(&api_key, value, &unit).await }
-                })
-                .await;
-
-                match result {
-                    Ok(()) => {
-                        info!(
-                            value = reading.value,
-                            unit = %reading.unit,
-                            "Data sent successfully to Supabase"
-                        );
+    loop {
+        interval.tick().await;
+        info!(source = %task.name, "Polling data source");
+
+        let mut outgoing = Vec::new();
+        let disabled = task.source.disabled_count();
+        let result = task.source.read_values(&metrics).await;
+        if task.source.disabled_count() > disabled {
+            write_status(&metrics);
+        }
+
+        match result {
+            Ok(readings) => {
+                if readings.is_empty() {
+                    debug!(source = %task.name, "No new readings from source");
+                }
+
+                for reading in readings {
+                    if let Err(e) = task.filter.check(&reading) {
+                        warn!(error = %e, source = %task.name, "Dropping reading");
+                        metrics.record_dropped(&task.name);
+                        continue;
                     }
-                    Err(e) => {
-                        error!(
-                            error = %e,
-                            value = reading.value,
-                            "Failed to send data to Supabase after retries"
-                        );
+
+                    info!(
+                        value = reading.value,
+                        unit = %reading.unit,
+                        source = %task.name,
+                        "Read value from source"
+                    );
+                    metrics.record_read(&task.name, reading.value, &reading.unit);
+
+                    match task.aggregator.as_mut() {
+                        Some(aggregator) if aggregator.is_late(&reading) => {
+                            warn!(
+                                source = %task.name,
+                                timestamp = %reading.timestamp,
+                                "Dropping reading for a window already sent"
+                            );
+                            metrics.record_late_dropped(&task.name);
+                        }
+                        Some(aggregator) => outgoing.extend(aggregator.push(reading)),
+                        None => outgoing.push(reading),
                     }
                 }
             }
             Err(e) => {
                 warn!(
                     error = %e,
-                    source = self.source.source_id(),
+                    source = %task.name,
                     "Failed to read value from source"
                 );
+                metrics.record_read_failure(&task.name);
             }
         }
+
+        if let Some(aggregator) = task.aggregator.as_mut() {
+            outgoing.extend(aggregator.flush(Utc::now()));
+        }
+
+        for reading in outgoing {
+            let entry = OutboxEntry {
+                api_key: task.api_key.clone(),
+                reading,
+            };
+            if readings_tx.send(entry).await.is_err() {
+                return;
+            }
+        }
+
+        // Only now may a source move its saved position past these readings
+        if let Err(e) = task.source.commit().await {
+            warn!(error = %e, source = %task.name, "Failed to save source position");
+        }
+
+        if task.source.is_disabled() {
+            error!(source = %task.name, "No source left to read, stopped polling");
+            return;
+        }
+    }
+}
+
+/// Update the status file `agentquelia status` reads.
+fn write_status(metrics: &Metrics) {
+    if let Err(e) = metrics.status().save() {
+        warn!(error = %e, "Failed to write the status file");
     }
 }
 
 pub struct AgentRunner {
     config: AgentConfig,
+    config_path: Option<PathBuf>,
     shutdown_tx: broadcast::Sender<()>,
+    metrics: Arc<Metrics>,
 }
 
 impl AgentRunner {
-    pub fn new(config: AgentConfig) -> Self {
+    /// `config_path` is watched for changes; `None` disables reloading.
+    pub fn new(config: AgentConfig, config_path: Option<PathBuf>) -> Self {
         let (shutdown_tx, _) = broadcast::channel(1);
         Self {
             config,
+            config_path,
             shutdown_tx,
+            metrics: Arc::new(Metrics::new()),
         }
     }
 
+    /// Run until a shutdown signal, restarting the pipeline whenever a valid new configuration is loaded.
+    ///
+    /// The running scheduler is stopped before the next one starts, so two configurations
+    /// never run side by side. An invalid file, or one whose sources, sinks or health
+    /// endpoint fail to start, leaves the previous configuration in place.
     pub async fn run(&self) -> Result<(), AgentError> {
-        let shutdown_rx = self.shutdown_tx.subscribe();
-        let mut scheduler = Scheduler::new(self.config.clone(), shutdown_rx)?;
+        let mut shutdown_rx = self.shutdown_tx.subscribe();
+        let mut reload_rx = self.spawn_reload_triggers();
 
         // Set up signal handlers
         let shutdown_tx = self.shutdown_tx.clone();
@@ -137,11 +303,172 @@ impl AgentRunner {
             let _ = shutdown_tx.send(());
         });
 
-        scheduler.run().await
+        let mut config = self.config.clone();
+        let mut previous: Option<AgentConfig> = None;
+        let mut health_server = self.start_health_server(&config).await?;
+
+        loop {
+            let (stop_tx, stop_rx) = broadcast::channel(1);
+            let mut scheduler = match Scheduler::new(config.clone(), stop_rx, self.metrics.clone())
+            {
+                Ok(scheduler) => scheduler,
+                Err(e) => match previous.take() {
+                    Some(old) => {
+                        error!(error = %e, "Failed to apply new configuration, keeping the current one");
+                        config = old;
+                        continue;
+                    }
+                    None => return Err(e),
+                },
+            };
+
+            if let Some(old) = previous.take() {
+                if old.health != config.health {
+                    if let Some(server) = health_server.take() {
+                        server.abort();
+                        // Wait for the listener to close so its address can be bound again
+                        let _ = server.await;
+                    }
+                    match self.start_health_server(&config).await {
+                        Ok(server) => health_server = server,
+                        Err(e) => {
+                            error!(error = %e, "Failed to apply new configuration, keeping the current one");
+                            health_server =
+                                self.start_health_server(&old).await.unwrap_or_else(|e| {
+                                    error!(error = %e, "Failed to restart the health endpoint");
+                                    None
+                                });
+                            config = old;
+                            continue;
+                        }
+                    }
+                }
+                self.metrics.retain(
+                    &config
+                        .sources
+                        .iter()
+                        .map(|s| s.name.as_str())
+                        .collect::<Vec<_>>(),
+                    &config
+                        .sinks
+                        .iter()
+                        .map(|s| s.name.as_str())
+                        .collect::<Vec<_>>(),
+                );
+                if old.logging != config.logging {
+                    warn!("Logging settings changed, restart the agent to apply them");
+                }
+                info!("Configuration reloaded");
+            }
+
+            let mut running = tokio::spawn(async move { scheduler.run().await });
+
+            let next = loop {
+                tokio::select! {
+                    result = &mut running => {
+                        // The scheduler only returns on its own if something went wrong
+                        if let Some(server) = health_server {
+                            server.abort();
+                        }
+                        return result.unwrap_or(Ok(()));
+                    }
+                    _ = shutdown_rx.recv() => {
+                        info!("Shutdown signal received");
+                        break None;
+                    }
+                    Some(()) = reload_rx.recv() => {
+                        if let Some(new_config) = self.reload_config(&config) {
+                            break Some(new_config);
+                        }
+                    }
+                }
+            };
+
+            let _ = stop_tx.send(());
+            if let Ok(Err(e)) = running.await {
+                error!(error = %e, "Scheduler stopped with an error");
+            }
+
+            match next {
+                Some(new_config) => previous = Some(std::mem::replace(&mut config, new_config)),
+                None => break,
+            }
+        }
+
+        if let Some(server) = health_server {
+            server.abort();
+        }
+        Ok(())
+    }
+
+    async fn start_health_server(
+        &self,
+        config: &AgentConfig,
+    ) -> Result<Option<JoinHandle<()>>, AgentError> {
+        if !config.health.enabled {
+            return Ok(None);
+        }
+        Ok(Some(
+            health::serve(&config.health.bind, self.metrics.clone()).await?,
+        ))
     }
 
-    pub fn shutdown(&self) {
-        let _ = self.shutdown_tx.send(());
+    /// Load and validate the configuration file again; `None` when it is unusable or unchanged.
+    fn reload_config(&self, current: &AgentConfig) -> Option<AgentConfig> {
+        info!("Reloading configuration");
+        match AgentConfig::load(self.config_path.as_deref()) {
+            Ok(config) if same_config(&config, current) => {
+                info!("Configuration unchanged");
+                None
+            }
+            Ok(config) => Some(config),
+            Err(e) => {
+                error!(error = %e, "Invalid configuration, keeping the current one");
+                None
+            }
+        }
+    }
+
+    /// Signal a reload when the config file changes and, on Unix, on SIGHUP.
+    fn spawn_reload_triggers(&self) -> mpsc::Receiver<()> {
+        // A pending reload already covers any change that arrives before it runs
+        let (reload_tx, reload_rx) = mpsc::channel(1);
+
+        if let Some(path) = self.config_path.clone() {
+            let reload_tx = reload_tx.clone();
+            tokio::spawn(async move {
+                let mut watcher = ConfigWatcher::new(path);
+                let mut interval = tokio::time::interval(CONFIG_POLL_INTERVAL);
+                interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+                loop {
+                    interval.tick().await;
+                    if watcher.changed() {
+                        info!(path = %watcher.path.display(), "Configuration file changed");
+                        let _ = reload_tx.try_send(());
+                    }
+                }
+            });
+        }
+
+        #[cfg(unix)]
+        tokio::spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let mut sighup = match signal(SignalKind::hangup()) {
+                Ok(sighup) => sighup,
+                Err(e) => {
+                    warn!(error = %e, "Failed to set up SIGHUP handler, only file changes trigger a reload");
+                    return;
+                }
+            };
+            while sighup.recv().await.is_some() {
+                info!("Received SIGHUP");
+                let _ = reload_tx.try_send(());
+            }
+        });
+
+        reload_rx
     }
 
     #[cfg(unix)]
@@ -169,3 +496,129 @@ impl AgentRunner {
         info!("Received Ctrl+C");
     }
 }
+
+/// Notices when the configuration file is written, replaced or removed.
+struct ConfigWatcher {
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    fn new(path: PathBuf) -> Self {
+        let last_modified = modified_time(&path);
+        Self {
+            path,
+            last_modified,
+        }
+    }
+
+    /// Whether the file changed since the previous call.
+    fn changed(&mut self) -> bool {
+        let modified = modified_time(&self.path);
+        if modified == self.last_modified {
+            return false;
+        }
+        self.last_modified = modified;
+        true
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn same_config(a: &AgentConfig, b: &AgentConfig) -> bool {
+    match (serde_json::to_value(a), serde_json::to_value(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config(path: &Path, interval: u64) {
+        std::fs::write(
+            path,
+            format!(
+                r#"
+                [agent]
+                instance_id = "site-1"
+                polling_interval_secs = {}
+
+                [poi]
+                api_key = "poi-key"
+
+                [supabase]
+                url = "https://example.supabase.co"
+                anon_key = "anon"
+
+                [source]
+                type = "json"
+
+                [source.json]
+                path = "/tmp/reading.json"
+                json_path = "$.power"
+                unit = "kW"
+                "#,
+                interval
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_same_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.toml");
+        write_config(&path, 60);
+        let config = AgentConfig::load(Some(&path)).unwrap();
+
+        let reloaded = AgentConfig::load(Some(&path)).unwrap();
+        assert!(same_config(&config, &reloaded));
+
+        let mut changed = config.clone();
+        changed.agent.polling_interval_secs = 30;
+        assert!(!same_config(&config, &changed));
+    }
+
+    #[test]
+    fn test_config_watcher_detects_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.toml");
+        write_config(&path, 60);
+        let mut watcher = ConfigWatcher::new(path.clone());
+        assert!(!watcher.changed());
+
+        // Some file systems only keep modification times to the second
+        std::thread::sleep(Duration::from_millis(1100));
+        write_config(&path, 30);
+        assert!(watcher.changed());
+        assert!(!watcher.changed());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(watcher.changed());
+    }
+
+    #[test]
+    fn test_reload_keeps_current_config_unless_valid_and_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.toml");
+        write_config(&path, 60);
+        let current = AgentConfig::load(Some(&path)).unwrap();
+        let runner = AgentRunner::new(current.clone(), Some(path.clone()));
+
+        assert!(runner.reload_config(&current).is_none());
+
+        std::fs::write(&path, "[agent\ninstance_id = ").unwrap();
+        assert!(runner.reload_config(&current).is_none());
+
+        write_config(&path, 0);
+        assert!(runner.reload_config(&current).is_none());
+
+        write_config(&path, 30);
+        let reloaded = runner.reload_config(&current).unwrap();
+        assert_eq!(reloaded.agent.polling_interval_secs, 30);
+    }
+}
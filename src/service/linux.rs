@@ -1,4 +1,3 @@
-use crate::config::AgentConfig;
 use crate::error::ServiceError;
 use std::fs;
 use std::path::PathBuf;
@@ -6,6 +5,9 @@ use std::process::Command;
 
 const SERVICE_NAME: &str = "agentquelia";
 
+/// Where the service reports disabled sources, readable by `agentquelia status` run as any user
+pub const STATUS_FILE: &str = "/var/lib/agentquelia/status.json";
+
 pub fn install(_user_level: bool) -> Result<(), ServiceError> {
     let service_file = PathBuf::from("/etc/systemd/system/agentquelia.service");
 
@@ -28,7 +30,9 @@ After=network.target
 [Service]
 Type=simple
 ExecStart={exe} run
+ExecReload=/bin/kill -HUP $MAINPID
 Environment=AGENTQUELIA_CONFIG={config}
+Environment=AGENTQUELIA_STATUS_FILE={status}
 Restart=always
 RestartSec=10
 StandardOutput=journal
@@ -40,6 +44,7 @@ WantedBy=multi-user.target
 "#,
         exe = exe_path.display(),
         config = config_path.display(),
+        status = STATUS_FILE,
     );
 
     fs::write(&service_file, service_content)
@@ -0,0 +1,95 @@
+use super::{DataSource, Reading};
+use crate::config::Unit;
+use crate::error::SourceError;
+use async_trait::async_trait;
+
+/// Wraps a source whose device reports in one unit so its readings come out in another.
+pub struct UnitConversion {
+    inner: Box<dyn DataSource>,
+    factor: f64,
+    unit: String,
+}
+
+impl UnitConversion {
+    pub fn new(inner: Box<dyn DataSource>, from: Unit, to: Unit) -> Result<Self, SourceError> {
+        let factor = from.factor_to(to).ok_or_else(|| {
+            SourceError::ParseError(format!(
+                "Cannot convert {} ({}) to {} ({})",
+                from,
+                from.quantity(),
+                to,
+                to.quantity()
+            ))
+        })?;
+
+        Ok(Self {
+            inner,
+            factor,
+            unit: to.to_string(),
+        })
+    }
+
+    fn convert(&self, mut reading: Reading) -> Reading {
+        reading.value *= self.factor;
+        reading.unit = self.unit.clone();
+        reading
+    }
+}
+
+#[async_trait]
+impl DataSource for UnitConversion {
+    async fn read_value(&self) -> Result<Reading, SourceError> {
+        Ok(self.convert(self.inner.read_value().await?))
+    }
+
+    async fn read_values(&self) -> Result<Vec<Reading>, SourceError> {
+        let readings = self.inner.read_values().await?;
+        Ok(readings.into_iter().map(|r| self.convert(r)).collect())
+    }
+
+    async fn commit(&self) -> Result<(), SourceError> {
+        self.inner.commit().await
+    }
+
+    fn source_id(&self) -> &str {
+        self.inner.source_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    struct Fixed(f64);
+
+    #[async_trait]
+    impl DataSource for Fixed {
+        async fn read_value(&self) -> Result<Reading, SourceError> {
+            Ok(Reading {
+                value: self.0,
+                unit: String::new(),
+                timestamp: Utc::now(),
+                source_id: "fixed".to_string(),
+            })
+        }
+
+        fn source_id(&self) -> &str {
+            "fixed"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_converts_to_target_unit() {
+        let source = UnitConversion::new(Box::new(Fixed(2500.0)), Unit::KW, Unit::MW).unwrap();
+        let reading = source.read_value().await.unwrap();
+        assert!((reading.value - 2.5).abs() < 1e-9);
+        assert_eq!(reading.unit, "MW");
+
+        let readings = source.read_values().await.unwrap();
+        assert_eq!(readings.len(), 1);
+        assert!((readings[0].value - 2.5).abs() < 1e-9);
+
+        assert!(UnitConversion::new(Box::new(Fixed(1.0)), Unit::KWh, Unit::MW).is_err());
+    }
+}
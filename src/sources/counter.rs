@@ -0,0 +1,214 @@
+use super::state::{load_state, save_state};
+use super::{DataSource, Reading};
+use crate::config::{CounterConfig, Unit};
+use crate::error::SourceError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+/// Register value and time of the last reading, persisted between runs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct CounterState {
+    value: f64,
+    timestamp: DateTime<Utc>,
+}
+
+/// Wraps a source reading a cumulative energy register and reports the average
+/// power between consecutive readings instead.
+pub struct EnergyCounter {
+    inner: Box<dyn DataSource>,
+    rollover: Option<f64>,
+    power_unit: String,
+    state_file: PathBuf,
+    last: Mutex<Option<CounterState>>,
+    /// What `state_file` holds; `last` is written there on `commit`
+    saved: Mutex<Option<CounterState>>,
+}
+
+impl EnergyCounter {
+    /// `energy_unit` is the unit of the register; readings come out in the matching power unit.
+    pub fn new(
+        inner: Box<dyn DataSource>,
+        config: &CounterConfig,
+        energy_unit: Unit,
+        state_file: PathBuf,
+    ) -> Self {
+        let state = load_state(&state_file);
+        Self {
+            inner,
+            rollover: config.rollover,
+            power_unit: energy_unit.power().to_string(),
+            last: Mutex::new(state),
+            saved: Mutex::new(state),
+            state_file,
+        }
+    }
+
+    /// The power between the previous register reading and this one, if it can be known.
+    fn advance(&self, last: &mut Option<CounterState>, reading: Reading) -> Option<Reading> {
+        let current = CounterState {
+            value: reading.value,
+            timestamp: reading.timestamp,
+        };
+        let Some(previous) = *last else {
+            debug!(source = %self.source_id(), value = current.value, "Energy counter baseline");
+            *last = Some(current);
+            return None;
+        };
+
+        // A repeated or out of order reading says nothing about the interval
+        let hours = (current.timestamp - previous.timestamp).num_milliseconds() as f64 / 3.6e6;
+        if hours <= 0.0 {
+            return None;
+        }
+        *last = Some(current);
+
+        let energy = if current.value >= previous.value {
+            current.value - previous.value
+        } else {
+            match self.rollover {
+                // Wrapped past its maximum, unless that would mean more than half a turn
+                Some(rollover) if rollover - previous.value + current.value < rollover / 2.0 => {
+                    rollover - previous.value + current.value
+                }
+                _ => {
+                    warn!(
+                        source = %self.source_id(),
+                        previous = previous.value,
+                        current = current.value,
+                        "Energy counter went backwards, treating it as a reset"
+                    );
+                    return None;
+                }
+            }
+        };
+
+        Some(Reading {
+            value: energy / hours,
+            unit: self.power_unit.clone(),
+            ..reading
+        })
+    }
+}
+
+#[async_trait]
+impl DataSource for EnergyCounter {
+    async fn read_value(&self) -> Result<Reading, SourceError> {
+        self.read_values().await?.pop().ok_or_else(|| {
+            SourceError::ValueNotFound("Energy counter needs a second reading".to_string())
+        })
+    }
+
+    async fn read_values(&self) -> Result<Vec<Reading>, SourceError> {
+        let readings = self.inner.read_values().await?;
+
+        let mut last = self.last.lock().unwrap();
+        Ok(readings
+            .into_iter()
+            .filter_map(|reading| self.advance(&mut last, reading))
+            .collect())
+    }
+
+    /// Save the register reading the last powers were computed up to.
+    async fn commit(&self) -> Result<(), SourceError> {
+        let last = *self.last.lock().unwrap();
+        {
+            let mut saved = self.saved.lock().unwrap();
+            if let Some(state) = last.filter(|state| Some(*state) != *saved) {
+                save_state(&self.state_file, &state)?;
+                *saved = Some(state);
+            }
+        }
+        self.inner.commit().await
+    }
+
+    fn source_id(&self) -> &str {
+        self.inner.source_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hands out queued register readings one poll at a time.
+    struct Register(Mutex<Vec<(f64, i64)>>);
+
+    #[async_trait]
+    impl DataSource for Register {
+        async fn read_value(&self) -> Result<Reading, SourceError> {
+            let (value, minutes) = self.0.lock().unwrap().remove(0);
+            Ok(Reading {
+                value,
+                unit: "kWh".to_string(),
+                timestamp: DateTime::<Utc>::UNIX_EPOCH + chrono::Duration::minutes(minutes),
+                source_id: "register".to_string(),
+            })
+        }
+
+        fn source_id(&self) -> &str {
+            "register"
+        }
+    }
+
+    fn counter(readings: Vec<(f64, i64)>, state_file: PathBuf) -> EnergyCounter {
+        let config = CounterConfig {
+            rollover: Some(100000.0),
+            state_file: None,
+        };
+        EnergyCounter::new(
+            Box::new(Register(Mutex::new(readings))),
+            &config,
+            Unit::KWh,
+            state_file,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_power_from_energy_register() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_file = dir.path().join("meter.counter.json");
+
+        let source = counter(
+            vec![
+                (1000.0, 0),
+                // 5 kWh in 15 minutes
+                (1005.0, 15),
+                // Wrapped at 100000: 99999 -> 3 is 4 kWh in 30 minutes
+                (99999.0, 30),
+                (3.0, 60),
+                // Meter replaced: no power for that interval
+                (1.0, 75),
+                (2.0, 90),
+            ],
+            state_file.clone(),
+        );
+
+        // The first reading is only a baseline
+        assert!(source.read_values().await.unwrap().is_empty());
+        let reading = source.read_value().await.unwrap();
+        assert!((reading.value - 20.0).abs() < 1e-9);
+        assert_eq!(reading.unit, "kW");
+
+        source.read_values().await.unwrap();
+        assert!((source.read_value().await.unwrap().value - 8.0).abs() < 1e-9);
+        assert!(source.read_values().await.unwrap().is_empty());
+        assert!((source.read_value().await.unwrap().value - 4.0).abs() < 1e-9);
+
+        // Nothing is saved until the readings have been handed over
+        assert!(!state_file.exists());
+        source.commit().await.unwrap();
+
+        // A restarted agent picks up from the saved reading
+        let source = counter(vec![(3.0, 105)], state_file.clone());
+        assert!((source.read_value().await.unwrap().value - 4.0).abs() < 1e-9);
+
+        // and again from there if it stops before handing that power over:
+        // 3 kWh in the 30 minutes since the saved reading
+        let source = counter(vec![(5.0, 120)], state_file);
+        assert!((source.read_value().await.unwrap().value - 6.0).abs() < 1e-9);
+    }
+}
@@ -1,21 +1,80 @@
-use super::{DataSource, Reading};
-use crate::config::CsvSourceConfig;
+use super::state::{load_state, save_state};
+use super::{DataSource, Expression, Freshness, Reading, TimestampParser};
+use crate::config::{CsvReadMode, CsvSourceConfig, NewestBy};
 use crate::error::SourceError;
 use async_trait::async_trait;
-use chrono::Utc;
-use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+/// How much of the end of the file to look at first when searching for the last row
+const TAIL_CHUNK_BYTES: u64 = 64 * 1024;
 
 pub struct CsvSource {
     path: PathBuf,
+    // Glob matching the candidate files when `path` doesn't name a single file
+    pattern: Option<String>,
+    newest_by: NewestBy,
     value_field: String,
+    expression: Option<Expression>,
+    variables: HashMap<String, String>,
     unit: String,
     read_last_row: bool,
     delimiter: u8,
     skip_headers: usize,
     multiplier: f64,
+    mode: CsvReadMode,
+    // Tail and incremental modes: where the previous poll stopped
+    tail: Mutex<Option<TailState>>,
+    cursor_file: Option<PathBuf>,
+    timestamp_field: Option<String>,
+    timestamp_parser: TimestampParser,
+    freshness: Freshness,
     source_id: String,
 }
 
+/// Where a row's fields are, resolved against the header line
+#[derive(Clone)]
+struct Columns {
+    /// The value column, or one column per expression variable
+    value: Vec<usize>,
+    timestamp: Option<usize>,
+}
+
+/// Position in a followed file, kept between polls.
+struct TailState {
+    path: PathBuf,
+    identity: Option<FileIdentity>,
+    /// Start of the first byte not parsed yet, always at a line boundary
+    offset: u64,
+    columns: Columns,
+    /// Data rows still to skip (`skip_headers`) when the file is read from the top
+    rows_to_skip: usize,
+    last_row: Option<(csv::StringRecord, Columns)>,
+    /// When incremental mode last read rows
+    read_at: Option<DateTime<Utc>>,
+}
+
+/// Distinguishes a rotated file from the one previously followed at the same path.
+type FileIdentity = (u64, u64);
+
+/// Incremental mode position, saved once a poll's readings have been handed over.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    #[serde(default)]
+    path: Option<PathBuf>,
+    identity: Option<FileIdentity>,
+    offset: u64,
+    #[serde(default)]
+    read_at: Option<DateTime<Utc>>,
+}
+
 impl CsvSource {
     pub fn new(config: &CsvSourceConfig) -> Result<Self, SourceError> {
         let delimiter = config
@@ -26,19 +85,456 @@ impl CsvSource {
             .try_into()
             .map_err(|_| SourceError::ParseError("Invalid delimiter".to_string()))?;
 
+        let pattern = match &config.file_pattern {
+            Some(file_pattern) => {
+                let dir = glob::Pattern::escape(&config.path.to_string_lossy());
+                Some(
+                    Path::new(&dir)
+                        .join(file_pattern)
+                        .to_string_lossy()
+                        .into_owned(),
+                )
+            }
+            None if is_glob(&config.path) => Some(config.path.to_string_lossy().into_owned()),
+            None => None,
+        };
+        if let Some(pattern) = &pattern {
+            glob::Pattern::new(pattern).map_err(|e| {
+                SourceError::ParseError(format!("Invalid file pattern '{}': {}", pattern, e))
+            })?;
+        }
+
         Ok(Self {
             path: config.path.clone(),
+            source_id: format!(
+                "csv:{}",
+                pattern.as_deref().unwrap_or(&config.path.to_string_lossy())
+            ),
+            pattern,
+            newest_by: config.newest_by,
             value_field: config.value_field.clone(),
+            expression: config
+                .expression
+                .as_deref()
+                .map(Expression::parse)
+                .transpose()?,
+            variables: config.variables.clone(),
             unit: config.unit.clone(),
             read_last_row: config.read_last_row,
             delimiter,
             skip_headers: config.skip_headers,
             multiplier: config.multiplier,
-            source_id: format!("csv:{}", config.path.display()),
+            mode: config.mode,
+            tail: Mutex::new(None),
+            cursor_file: config.cursor_file.clone(),
+            timestamp_field: config.timestamp_field.clone(),
+            timestamp_parser: TimestampParser::new(
+                config.timestamp_format.as_deref(),
+                config.timezone.as_deref(),
+            )?,
+            freshness: Freshness::new(config.max_age_secs, config.suppress_unchanged),
+        })
+    }
+
+    /// The file to read this poll: `path` itself, or the newest file matching the pattern.
+    fn current_path(&self) -> Result<PathBuf, SourceError> {
+        let Some(pattern) = &self.pattern else {
+            if !self.path.exists() {
+                return Err(SourceError::FileNotFound(self.path.display().to_string()));
+            }
+            return Ok(self.path.clone());
+        };
+
+        self.matching_files(pattern)?
+            .pop()
+            .ok_or_else(|| SourceError::FileNotFound(format!("No file matches {}", pattern)))
+    }
+
+    /// Files matching the pattern, oldest first by `newest_by`.
+    fn matching_files(&self, pattern: &str) -> Result<Vec<PathBuf>, SourceError> {
+        let files = glob::glob(pattern)
+            .map_err(|e| SourceError::ParseError(e.to_string()))?
+            .filter_map(Result::ok)
+            .filter(|p| p.is_file());
+
+        Ok(match self.newest_by {
+            NewestBy::Name => {
+                let mut files: Vec<_> = files.collect();
+                files.sort();
+                files
+            }
+            NewestBy::Modified => {
+                let mut files: Vec<_> = files
+                    .filter_map(|p| Some((p.metadata().and_then(|m| m.modified()).ok()?, p)))
+                    .collect();
+                files.sort();
+                files.into_iter().map(|(_, p)| p).collect()
+            }
         })
     }
 
-    fn parse_value(&self, content: &str) -> Result<f64, SourceError> {
+    fn column_index(headers: &csv::StringRecord, field: &str) -> Result<usize, SourceError> {
+        headers
+            .iter()
+            .position(|h| h == field)
+            .or_else(|| field.parse::<usize>().ok())
+            .ok_or_else(|| {
+                SourceError::ColumnNotFound(format!("Column '{}' not found in CSV", field))
+            })
+    }
+
+    fn columns(&self, headers: &csv::StringRecord) -> Result<Columns, SourceError> {
+        let value = match &self.expression {
+            Some(expression) => expression
+                .variables()
+                .iter()
+                .map(|name| {
+                    let field = self.variables.get(name).unwrap_or(name);
+                    Self::column_index(headers, field)
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec![Self::column_index(headers, &self.value_field)?],
+        };
+        let timestamp = self
+            .timestamp_field
+            .as_deref()
+            .map(|field| Self::column_index(headers, field))
+            .transpose()?;
+        Ok(Columns { value, timestamp })
+    }
+
+    fn field_at(row: &csv::StringRecord, column_idx: usize) -> Result<&str, SourceError> {
+        row.get(column_idx).ok_or_else(|| {
+            SourceError::ValueNotFound(format!("Column index {} out of bounds", column_idx))
+        })
+    }
+
+    fn reading_at(
+        &self,
+        row: &csv::StringRecord,
+        columns: &Columns,
+    ) -> Result<Reading, SourceError> {
+        let values = columns
+            .value
+            .iter()
+            .map(|&column_idx| {
+                let value_str = Self::field_at(row, column_idx)?;
+                value_str
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|_| SourceError::InvalidValueType(format!("'{}'", value_str)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let value = match &self.expression {
+            Some(expression) => expression.evaluate(&values)?,
+            None => values[0],
+        };
+
+        let timestamp = match columns.timestamp {
+            Some(idx) => self.timestamp_parser.parse(Self::field_at(row, idx)?)?,
+            None => Utc::now(),
+        };
+
+        Ok(Reading {
+            value: value * self.multiplier,
+            unit: self.unit.clone(),
+            timestamp,
+            source_id: self.source_id.clone(),
+        })
+    }
+
+    /// Full and tail modes: the configured row, along with the file's modification time.
+    async fn read_latest(&self, path: &Path) -> Result<(Reading, DateTime<Utc>), SourceError> {
+        let modified = self.freshness.check_modified(path).await?;
+
+        let reading = match self.mode {
+            CsvReadMode::Tail => self.read_tail(path).await?,
+            _ => {
+                // Read the file content
+                let content = tokio::fs::read_to_string(path)
+                    .await
+                    .map_err(|e| SourceError::ReadError(e.to_string()))?;
+
+                self.parse_value(&content)?
+            }
+        };
+
+        if self.timestamp_field.is_some() {
+            self.freshness.check_timestamp(reading.timestamp)?;
+        }
+        Ok((reading, modified))
+    }
+
+    /// Read the last row of an append-only file, parsing only what was added since the previous poll.
+    async fn read_tail(&self, path: &Path) -> Result<Reading, SourceError> {
+        let mut tail = self.tail.lock().await;
+        self.read_new_rows(&mut tail, path).await?;
+
+        let state = tail
+            .as_ref()
+            .expect("tail state initialized by read_new_rows");
+        let (row, columns) = state
+            .last_row
+            .as_ref()
+            .ok_or_else(|| SourceError::CsvError("No data rows found in CSV".to_string()))?;
+        self.reading_at(row, columns)
+    }
+
+    /// Turn every row added since the previous poll into a reading.
+    ///
+    /// The cursor is only saved by `commit`, so rows read just before a crash are
+    /// read again on restart rather than lost.
+    async fn read_incremental(&self, path: &Path) -> Result<Vec<Reading>, SourceError> {
+        let mut tail = self.tail.lock().await;
+        let rows = self.read_new_rows(&mut tail, path).await?;
+        let state = tail
+            .as_mut()
+            .expect("tail state initialized by read_new_rows");
+        let now = Utc::now();
+
+        let mut readings = Vec::with_capacity(rows.len());
+        for (i, (row, columns)) in rows.iter().enumerate() {
+            match self.reading_at(row, columns) {
+                Ok(mut reading) => {
+                    if columns.timestamp.is_none() {
+                        reading.timestamp = spread_timestamp(state.read_at, now, i, rows.len());
+                    }
+                    readings.push(reading);
+                }
+                // One bad row must not hold back the rows after it
+                Err(e) => warn!(path = %path.display(), error = %e, "Skipping CSV row"),
+            }
+        }
+        if !rows.is_empty() {
+            state.read_at = Some(now);
+        }
+        drop(tail);
+
+        // Nothing new is only a problem once the file is also old
+        if readings.is_empty() {
+            self.freshness.check_modified(path).await?;
+        }
+
+        Ok(readings)
+    }
+
+    /// Parse the rows appended since the previous call, with the columns to read them by.
+    ///
+    /// When a newer file has replaced the one followed so far, the rest of the previous
+    /// file is read first, then any file that came between them, then the new one from
+    /// the top.
+    async fn read_new_rows(
+        &self,
+        tail: &mut Option<TailState>,
+        path: &Path,
+    ) -> Result<Vec<(csv::StringRecord, Columns)>, SourceError> {
+        let previous = match tail.as_ref() {
+            Some(state) => Some(state.path.clone()),
+            None => self
+                .cursor_file
+                .as_deref()
+                .and_then(load_state::<Cursor>)
+                .and_then(|cursor| cursor.path),
+        };
+
+        let mut rows = Vec::new();
+        if let Some(previous) = previous.filter(|p| p != path && p.exists()) {
+            // Rotated more than once since the previous read: the files in between too
+            let between = match &self.pattern {
+                Some(pattern) => {
+                    let files = self.matching_files(pattern)?;
+                    match files.iter().position(|p| *p == previous) {
+                        Some(i) => files[i + 1..]
+                            .iter()
+                            .filter(|p| *p != path)
+                            .cloned()
+                            .collect(),
+                        None => Vec::new(),
+                    }
+                }
+                None => Vec::new(),
+            };
+
+            for file in std::iter::once(previous).chain(between) {
+                info!(
+                    from = %file.display(),
+                    to = %path.display(),
+                    "Moving to the next CSV file"
+                );
+                match self.follow(tail, &file).await {
+                    Ok(file_rows) => {
+                        let columns = &tail
+                            .as_ref()
+                            .expect("tail state initialized by follow")
+                            .columns;
+                        rows.extend(file_rows.into_iter().map(|row| (row, columns.clone())));
+                    }
+                    Err(e) => warn!(
+                        path = %file.display(),
+                        error = %e,
+                        "Could not read the end of an earlier CSV file"
+                    ),
+                }
+            }
+        }
+
+        let new_rows = self.follow(tail, path).await?;
+        let columns = &tail
+            .as_ref()
+            .expect("tail state initialized by follow")
+            .columns;
+        rows.extend(new_rows.into_iter().map(|row| (row, columns.clone())));
+        Ok(rows)
+    }
+
+    /// Parse the rows appended to `path` since the previous call.
+    ///
+    /// The first call starts at the last row of the file, or where the saved cursor
+    /// points in incremental mode. When the file shrinks or is replaced (log rotation)
+    /// it is read again from the top. Only newline-terminated rows are considered, so
+    /// a row still being written is picked up on the next poll.
+    async fn follow(
+        &self,
+        tail: &mut Option<TailState>,
+        path: &Path,
+    ) -> Result<Vec<csv::StringRecord>, SourceError> {
+        let mut file = File::open(path)
+            .await
+            .map_err(|e| SourceError::ReadError(e.to_string()))?;
+        let metadata = file
+            .metadata()
+            .await
+            .map_err(|e| SourceError::ReadError(e.to_string()))?;
+        let len = metadata.len();
+        let identity = file_identity(&metadata);
+
+        let rotated = match tail.as_ref() {
+            Some(state) => state.path != path || state.identity != identity || len < state.offset,
+            None => false,
+        };
+        if rotated && tail.as_ref().is_some_and(|state| state.path == path) {
+            info!(
+                path = %path.display(),
+                "CSV file was truncated or replaced, reading it from the start"
+            );
+        }
+
+        if tail.is_none() || rotated {
+            let (headers, header_end) = self.read_header(&mut file).await?;
+            let previous = tail.take();
+            let mut state = TailState {
+                path: path.to_path_buf(),
+                identity,
+                offset: header_end,
+                columns: self.columns(&headers)?,
+                rows_to_skip: self.skip_headers,
+                read_at: previous.as_ref().and_then(|state| state.read_at),
+                // Keep serving the previous value until the new file has a row
+                last_row: previous.and_then(|state| state.last_row),
+            };
+
+            if !rotated {
+                let saved = self.cursor_file.as_deref().and_then(load_state::<Cursor>);
+                match saved {
+                    // Resume where the previous run stopped
+                    Some(cursor)
+                        if cursor.identity == identity
+                            && (header_end..=len).contains(&cursor.offset) =>
+                    {
+                        state.offset = cursor.offset;
+                        state.rows_to_skip = 0;
+                        state.read_at = cursor.read_at;
+                    }
+                    // Replaced while the agent was stopped, all of it is new
+                    Some(_) => {
+                        info!(
+                            path = %path.display(),
+                            "CSV file changed since the last run, reading it from the start"
+                        );
+                    }
+                    // Skip the history instead of parsing it all
+                    None => {
+                        let last_row_start =
+                            find_last_row_start(&mut file, header_end, len).await?;
+                        if last_row_start > header_end {
+                            state.offset = last_row_start;
+                            state.rows_to_skip = 0;
+                        }
+                    }
+                }
+            }
+            *tail = Some(state);
+        }
+
+        let state = tail.as_mut().expect("tail state initialized above");
+        let mut rows = Vec::new();
+        if len > state.offset {
+            let new_bytes = read_range(&mut file, state.offset, len).await?;
+            let complete = new_bytes
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .map_or(0, |i| i + 1);
+
+            let mut reader = csv::ReaderBuilder::new()
+                .delimiter(self.delimiter)
+                .has_headers(false)
+                .flexible(true)
+                .from_reader(&new_bytes[..complete]);
+
+            for record in reader.records().filter_map(|r| r.ok()) {
+                if state.rows_to_skip > 0 {
+                    state.rows_to_skip -= 1;
+                    continue;
+                }
+                rows.push(record);
+            }
+            if let Some(last) = rows.last() {
+                state.last_row = Some((last.clone(), state.columns.clone()));
+            }
+            state.offset += complete as u64;
+        }
+
+        Ok(rows)
+    }
+
+    /// Parse the header line, returning it with the offset of the first data row.
+    async fn read_header(&self, file: &mut File) -> Result<(csv::StringRecord, u64), SourceError> {
+        let mut line = Vec::new();
+        let mut buf = [0u8; 4096];
+        file.seek(SeekFrom::Start(0))
+            .await
+            .map_err(|e| SourceError::ReadError(e.to_string()))?;
+
+        let header_end = loop {
+            let n = file
+                .read(&mut buf)
+                .await
+                .map_err(|e| SourceError::ReadError(e.to_string()))?;
+            if n == 0 {
+                return Err(SourceError::CsvError(
+                    "CSV header line is incomplete".to_string(),
+                ));
+            }
+            if let Some(i) = buf[..n].iter().position(|&b| b == b'\n') {
+                line.extend_from_slice(&buf[..i]);
+                break line.len() as u64 + 1;
+            }
+            line.extend_from_slice(&buf[..n]);
+        };
+
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(true)
+            .from_reader(line.as_slice());
+        let headers = reader
+            .headers()
+            .map_err(|e| SourceError::CsvError(e.to_string()))?
+            .clone();
+
+        Ok((headers, header_end))
+    }
+
+    pub(super) fn parse_value(&self, content: &str) -> Result<Reading, SourceError> {
         let mut reader = csv::ReaderBuilder::new()
             .delimiter(self.delimiter)
             .has_headers(true)
@@ -49,17 +545,8 @@ impl CsvSource {
             .map_err(|e| SourceError::CsvError(e.to_string()))?
             .clone();
 
-        // Find the column index for the value field
-        let column_idx = headers
-            .iter()
-            .position(|h| h == self.value_field)
-            .or_else(|| self.value_field.parse::<usize>().ok())
-            .ok_or_else(|| {
-                SourceError::ValueNotFound(format!(
-                    "Column '{}' not found in CSV",
-                    self.value_field
-                ))
-            })?;
+        // Find the column indexes for the value and timestamp fields
+        let columns = self.columns(&headers)?;
 
         // Read all records
         let records: Vec<csv::StringRecord> = reader
@@ -69,7 +556,8 @@ impl CsvSource {
             .collect();
 
         if records.is_empty() {
-            return Err(SourceError::ValueNotFound(
+            // A file that was just started: not a missing column
+            return Err(SourceError::CsvError(
                 "No data rows found in CSV".to_string(),
             ));
         }
@@ -83,42 +571,124 @@ impl CsvSource {
         .ok_or_else(|| SourceError::ValueNotFound("No rows found".to_string()))?;
 
         // Get the value from the column
-        let value_str = row.get(column_idx).ok_or_else(|| {
-            SourceError::ValueNotFound(format!("Column index {} out of bounds", column_idx))
-        })?;
+        self.reading_at(row, &columns)
+    }
+}
+
+async fn read_range(file: &mut File, start: u64, end: u64) -> Result<Vec<u8>, SourceError> {
+    let mut bytes = Vec::with_capacity((end - start) as usize);
+    file.seek(SeekFrom::Start(start))
+        .await
+        .map_err(|e| SourceError::ReadError(e.to_string()))?;
+    file.take(end - start)
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(|e| SourceError::ReadError(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// Offset of the last newline-terminated row after `data_start`, or `data_start` if there is none.
+async fn find_last_row_start(
+    file: &mut File,
+    data_start: u64,
+    len: u64,
+) -> Result<u64, SourceError> {
+    let mut chunk = TAIL_CHUNK_BYTES;
+    loop {
+        let start = len.saturating_sub(chunk).max(data_start);
+        let bytes = read_range(file, start, len).await?;
 
-        // Parse the value as f64
-        value_str
-            .trim()
-            .parse::<f64>()
-            .map_err(|_| SourceError::InvalidValueType(format!("'{}'", value_str)))
+        // The last complete row ends at the last newline and starts after the one before it
+        let row_start = bytes
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .and_then(|end| bytes[..end].iter().rposition(|&b| b == b'\n'));
+
+        match row_start {
+            Some(i) => return Ok(start + i as u64 + 1),
+            None if start == data_start => return Ok(data_start),
+            // A row longer than the chunk, look further back
+            None => chunk *= 2,
+        }
     }
 }
 
+/// Time of the `index`-th of `count` rows read together when the file has no timestamp
+/// column: spaced evenly since the previous read, the last one at `now`.
+fn spread_timestamp(
+    since: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    index: usize,
+    count: usize,
+) -> DateTime<Utc> {
+    match since.filter(|since| *since < now) {
+        Some(since) => now - (now - since) / count as i32 * (count - 1 - index) as i32,
+        None => now,
+    }
+}
+
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+#[cfg(unix)]
+fn file_identity(metadata: &std::fs::Metadata) -> Option<FileIdentity> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(metadata: &std::fs::Metadata) -> Option<FileIdentity> {
+    let created = metadata.created().ok()?;
+    let since_epoch = created.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some((since_epoch.as_secs(), since_epoch.subsec_nanos() as u64))
+}
+
 #[async_trait]
 impl DataSource for CsvSource {
+    /// The latest row. In incremental mode this parses the whole file and leaves
+    /// the cursor alone; polling goes through `read_values`.
     async fn read_value(&self) -> Result<Reading, SourceError> {
-        // Check if file exists
-        if !self.path.exists() {
-            return Err(SourceError::FileNotFound(
-                self.path.display().to_string(),
-            ));
+        // Find the file to read and check it exists
+        let path = self.current_path()?;
+        Ok(self.read_latest(&path).await?.0)
+    }
+
+    async fn read_values(&self) -> Result<Vec<Reading>, SourceError> {
+        let path = self.current_path()?;
+
+        if self.mode == CsvReadMode::Incremental {
+            return self.read_incremental(&path).await;
         }
 
-        // Read the file content
-        let content = tokio::fs::read_to_string(&self.path)
-            .await
-            .map_err(|e| SourceError::ReadError(e.to_string()))?;
+        let (reading, modified) = self.read_latest(&path).await?;
+        let version = match self.timestamp_field {
+            Some(_) => reading.timestamp,
+            None => modified,
+        };
+        if self.freshness.is_repeat(reading.value, version) {
+            return Ok(Vec::new());
+        }
+        Ok(vec![reading])
+    }
 
-        let raw_value = self.parse_value(&content)?;
-        let value = raw_value * self.multiplier;
+    /// Save the incremental mode position reached by the last `read_values`.
+    async fn commit(&self) -> Result<(), SourceError> {
+        let (Some(cursor_file), CsvReadMode::Incremental) = (&self.cursor_file, self.mode) else {
+            return Ok(());
+        };
+        let tail = self.tail.lock().await;
+        let Some(state) = tail.as_ref() else {
+            return Ok(());
+        };
 
-        Ok(Reading {
-            value,
-            unit: self.unit.clone(),
-            timestamp: Utc::now(),
-            source_id: self.source_id.clone(),
-        })
+        let cursor = Cursor {
+            path: Some(state.path.clone()),
+            identity: state.identity,
+            offset: state.offset,
+            read_at: state.read_at,
+        };
+        save_state(cursor_file, &cursor)
     }
 
     fn source_id(&self) -> &str {
@@ -143,11 +713,22 @@ mod tests {
         let config = CsvSourceConfig {
             path: file.path().to_path_buf(),
             value_field: "power_kw".to_string(),
+            expression: None,
+            variables: HashMap::new(),
             unit: "kW".to_string(),
             read_last_row: true,
             delimiter: ",".to_string(),
             skip_headers: 0,
             multiplier: 1.0,
+            mode: CsvReadMode::Full,
+            cursor_file: None,
+            timestamp_field: None,
+            timestamp_format: None,
+            timezone: None,
+            max_age_secs: None,
+            suppress_unchanged: false,
+            file_pattern: None,
+            newest_by: NewestBy::Name,
         };
 
         let source = CsvSource::new(&config).unwrap();
@@ -167,11 +748,22 @@ mod tests {
         let config = CsvSourceConfig {
             path: file.path().to_path_buf(),
             value_field: "power_kw".to_string(),
+            expression: None,
+            variables: HashMap::new(),
             unit: "kW".to_string(),
             read_last_row: false,
             delimiter: ",".to_string(),
             skip_headers: 0,
             multiplier: 1.0,
+            mode: CsvReadMode::Full,
+            cursor_file: None,
+            timestamp_field: None,
+            timestamp_format: None,
+            timezone: None,
+            max_age_secs: None,
+            suppress_unchanged: false,
+            file_pattern: None,
+            newest_by: NewestBy::Name,
         };
 
         let source = CsvSource::new(&config).unwrap();
@@ -179,4 +771,287 @@ mod tests {
 
         assert!((reading.value - 100.5).abs() < 0.001);
     }
+
+    fn tail_config(path: &std::path::Path) -> CsvSourceConfig {
+        CsvSourceConfig {
+            path: path.to_path_buf(),
+            value_field: "power_kw".to_string(),
+            expression: None,
+            variables: HashMap::new(),
+            unit: "kW".to_string(),
+            read_last_row: true,
+            delimiter: ",".to_string(),
+            skip_headers: 0,
+            multiplier: 1.0,
+            mode: CsvReadMode::Tail,
+            cursor_file: None,
+            timestamp_field: None,
+            timestamp_format: None,
+            timezone: None,
+            max_age_secs: None,
+            suppress_unchanged: false,
+            file_pattern: None,
+            newest_by: NewestBy::Name,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_csv_tail_follows_appended_rows() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "timestamp,power_kw").unwrap();
+        for i in 0..10_000 {
+            writeln!(file, "2024-01-01T00:00:{:05},{}", i, i).unwrap();
+        }
+
+        let source = CsvSource::new(&tail_config(file.path())).unwrap();
+        assert_eq!(source.read_value().await.unwrap().value, 9999.0);
+
+        // Nothing new: the last row is served again
+        assert_eq!(source.read_value().await.unwrap().value, 9999.0);
+
+        // A partially written row is ignored until its newline arrives
+        writeln!(file, "2024-01-02T00:00:00,42.5").unwrap();
+        write!(file, "2024-01-02T00:01:00,4").unwrap();
+        file.flush().unwrap();
+        assert_eq!(source.read_value().await.unwrap().value, 42.5);
+
+        writeln!(file, "3.5").unwrap();
+        assert_eq!(source.read_value().await.unwrap().value, 43.5);
+    }
+
+    #[tokio::test]
+    async fn test_csv_tail_handles_truncation_and_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.csv");
+        std::fs::write(&path, "timestamp,power_kw\nt1,10\nt2,20\nt3,30\n").unwrap();
+
+        let source = CsvSource::new(&tail_config(&path)).unwrap();
+        assert_eq!(source.read_value().await.unwrap().value, 30.0);
+
+        // Truncated in place
+        std::fs::write(&path, "timestamp,power_kw\nt4,5\n").unwrap();
+        assert_eq!(source.read_value().await.unwrap().value, 5.0);
+
+        // Replaced by a new, larger file with different columns
+        let rotated = dir.path().join("log.csv.new");
+        std::fs::write(
+            &rotated,
+            "power_kw,timestamp\n1,t5\n2,t6\n3,t7\n4,t8\n5,t9\n",
+        )
+        .unwrap();
+        std::fs::rename(&rotated, &path).unwrap();
+        assert_eq!(source.read_value().await.unwrap().value, 5.0);
+    }
+
+    #[tokio::test]
+    async fn test_csv_incremental_returns_every_new_row_and_resumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.csv");
+        std::fs::write(&path, "timestamp,power_kw\nt1,10\nt2,20\n").unwrap();
+
+        let mut config = tail_config(&path);
+        config.mode = CsvReadMode::Incremental;
+        config.cursor_file = Some(dir.path().join("cursor.json"));
+
+        let values = |readings: Vec<Reading>| readings.iter().map(|r| r.value).collect::<Vec<_>>();
+
+        // First run starts at the last row rather than replaying the history
+        let source = CsvSource::new(&config).unwrap();
+        assert_eq!(values(source.read_values().await.unwrap()), vec![20.0]);
+        assert!(source.read_values().await.unwrap().is_empty());
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        writeln!(file, "t3,30\nt4,40\nt5,50").unwrap();
+        assert_eq!(
+            values(source.read_values().await.unwrap()),
+            vec![30.0, 40.0, 50.0]
+        );
+
+        // Rows written while the agent is stopped are picked up on restart
+        source.commit().await.unwrap();
+        drop(source);
+        writeln!(file, "t6,60\nt7,70").unwrap();
+        let source = CsvSource::new(&config).unwrap();
+        let readings = source.read_values().await.unwrap();
+        assert_eq!(values(readings.clone()), vec![60.0, 70.0]);
+        // No timestamp column: spaced over the time since the previous read
+        assert!(readings[0].timestamp < readings[1].timestamp);
+
+        // Rows read but never handed over are read again after a crash
+        drop(source);
+        let source = CsvSource::new(&config).unwrap();
+        assert_eq!(
+            values(source.read_values().await.unwrap()),
+            vec![60.0, 70.0]
+        );
+
+        // Reading the latest value leaves the cursor alone
+        writeln!(file, "t8,80").unwrap();
+        assert_eq!(source.read_value().await.unwrap().value, 80.0);
+        assert_eq!(values(source.read_values().await.unwrap()), vec![80.0]);
+    }
+
+    #[tokio::test]
+    async fn test_csv_timestamp_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.csv");
+        std::fs::write(
+            &path,
+            "date;power_kw\n15/01/2024 12:00;10\n15/07/2024 12:00;20\n",
+        )
+        .unwrap();
+
+        let mut config = tail_config(&path);
+        config.delimiter = ";".to_string();
+        config.mode = CsvReadMode::Full;
+        config.timestamp_field = Some("date".to_string());
+        config.timestamp_format = Some("%d/%m/%Y %H:%M".to_string());
+        config.timezone = Some("Europe/Paris".to_string());
+
+        let source = CsvSource::new(&config).unwrap();
+        let reading = source.read_value().await.unwrap();
+        assert_eq!(reading.timestamp.to_rfc3339(), "2024-07-15T10:00:00+00:00");
+
+        // A row whose timestamp can't be parsed is skipped in incremental mode
+        config.mode = CsvReadMode::Incremental;
+        config.cursor_file = Some(dir.path().join("cursor.json"));
+        let source = CsvSource::new(&config).unwrap();
+        source.read_values().await.unwrap();
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        writeln!(file, "soon;30\n15/01/2025 08:30;40").unwrap();
+        let readings = source.read_values().await.unwrap();
+        assert_eq!(readings.len(), 1);
+        assert_eq!(
+            readings[0].timestamp.to_rfc3339(),
+            "2025-01-15T07:30:00+00:00"
+        );
+
+        config.timestamp_field = Some("time".to_string());
+        let source = CsvSource::new(&config).unwrap();
+        assert!(matches!(
+            source.read_value().await,
+            Err(SourceError::ColumnNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_csv_stale_and_unchanged_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.csv");
+        let now = Utc::now();
+        std::fs::write(
+            &path,
+            format!("timestamp,power_kw\n{},10\n", now.to_rfc3339()),
+        )
+        .unwrap();
+
+        let mut config = tail_config(&path);
+        config.timestamp_field = Some("timestamp".to_string());
+        config.max_age_secs = Some(600);
+        config.suppress_unchanged = true;
+        let source = CsvSource::new(&config).unwrap();
+
+        assert_eq!(source.read_values().await.unwrap().len(), 1);
+        // Same row on the next poll: nothing to send, but not stale either
+        assert!(source.read_values().await.unwrap().is_empty());
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        let old = now - chrono::Duration::hours(1);
+        writeln!(file, "{},20", old.to_rfc3339()).unwrap();
+        assert!(matches!(
+            source.read_values().await,
+            Err(SourceError::Stale(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_csv_glob_moves_to_the_next_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let day = |d: u32| dir.path().join(format!("power_2026-10-{}.csv", d));
+        std::fs::write(day(16), "timestamp,power_kw\nt1,1\nt2,2\n").unwrap();
+
+        let mut config = tail_config(&dir.path().join("power_*.csv"));
+        config.mode = CsvReadMode::Incremental;
+        config.cursor_file = Some(dir.path().join("state").join("cursor.json"));
+
+        let values = |readings: Vec<Reading>| readings.iter().map(|r| r.value).collect::<Vec<_>>();
+        let append = |path: PathBuf, rows: &str| {
+            let mut file = std::fs::OpenOptions::new().append(true).open(path).unwrap();
+            write!(file, "{}", rows).unwrap();
+        };
+
+        let source = CsvSource::new(&config).unwrap();
+        assert_eq!(values(source.read_values().await.unwrap()), vec![2.0]);
+
+        // Rows written to the old file just before rollover are not lost
+        append(day(16), "t3,3\n");
+        std::fs::write(day(17), "timestamp,power_kw\nt4,4\n").unwrap();
+        assert_eq!(values(source.read_values().await.unwrap()), vec![3.0, 4.0]);
+
+        // Same across a restart
+        source.commit().await.unwrap();
+        drop(source);
+        append(day(17), "t5,5\n");
+        std::fs::write(day(18), "timestamp,power_kw\nt6,6\n").unwrap();
+        let source = CsvSource::new(&config).unwrap();
+        assert_eq!(values(source.read_values().await.unwrap()), vec![5.0, 6.0]);
+
+        // Two rotations while the agent is stopped: the file in between is read too
+        source.commit().await.unwrap();
+        drop(source);
+        append(day(18), "t7,7\n");
+        std::fs::write(day(19), "timestamp,power_kw\nt8,8\nt9,9\n").unwrap();
+        std::fs::write(day(20), "timestamp,power_kw\nt10,10\n").unwrap();
+        let source = CsvSource::new(&config).unwrap();
+        assert_eq!(
+            values(source.read_values().await.unwrap()),
+            vec![7.0, 8.0, 9.0, 10.0]
+        );
+
+        // Directory plus pattern, whole file
+        config.path = dir.path().to_path_buf();
+        config.file_pattern = Some("power_*.csv".to_string());
+        config.mode = CsvReadMode::Full;
+        let source = CsvSource::new(&config).unwrap();
+        assert_eq!(source.read_value().await.unwrap().value, 10.0);
+
+        config.file_pattern = Some("energy_*.csv".to_string());
+        let source = CsvSource::new(&config).unwrap();
+        assert!(matches!(
+            source.read_value().await,
+            Err(SourceError::FileNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_csv_expression_over_columns() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, "timestamp,P1,P2,Power L3 (W),status").unwrap();
+        writeln!(file, "2024-01-01,1000,1500,2500,ok").unwrap();
+
+        let mut config = tail_config(file.path());
+        config.mode = CsvReadMode::Full;
+        config.value_field = String::new();
+        config.expression = Some("sum(P1, P2, P3) / 1000".to_string());
+        config.variables = HashMap::from([("P3".to_string(), "Power L3 (W)".to_string())]);
+
+        let source = CsvSource::new(&config).unwrap();
+        assert_eq!(source.read_value().await.unwrap().value, 5.0);
+
+        config.expression = Some("P1 + P4".to_string());
+        let source = CsvSource::new(&config).unwrap();
+        assert!(matches!(
+            source.read_value().await,
+            Err(SourceError::ColumnNotFound(_))
+        ));
+    }
 }
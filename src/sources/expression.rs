@@ -0,0 +1,331 @@
+use crate::error::SourceError;
+
+/// Arithmetic over named inputs, e.g. `sum(P1, P2, P3)` or `V * I / 1000`.
+///
+/// Supports numbers, `+ - * /`, parentheses and the functions `sum`, `avg`,
+/// `min` and `max`. Names are resolved by the source (CSV columns, JSONPaths);
+/// nothing else can be referenced or called.
+#[derive(Debug)]
+pub struct Expression {
+    root: Node,
+    variables: Vec<String>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Number(f64),
+    /// Index into `Expression::variables`
+    Variable(usize),
+    Negate(Box<Node>),
+    Binary(Box<Node>, Operator, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Function {
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Self, SourceError> {
+        let invalid = |reason: String| {
+            SourceError::ParseError(format!("Invalid expression '{}': {}", text, reason))
+        };
+
+        let mut parser = Parser {
+            tokens: tokenize(text).map_err(invalid)?,
+            position: 0,
+            variables: Vec::new(),
+        };
+        let root = parser.expression().map_err(invalid)?;
+        if let Some(token) = parser.peek() {
+            return Err(invalid(format!("unexpected {}", describe(token))));
+        }
+
+        Ok(Self {
+            root,
+            variables: parser.variables,
+        })
+    }
+
+    /// Names used by the expression, in the order `evaluate` expects their values.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn evaluate(&self, values: &[f64]) -> Result<f64, SourceError> {
+        let result = eval(&self.root, values);
+        if result.is_finite() {
+            Ok(result)
+        } else {
+            Err(SourceError::InvalidValueType(format!(
+                "expression result {} (division by zero?)",
+                result
+            )))
+        }
+    }
+}
+
+fn eval(node: &Node, values: &[f64]) -> f64 {
+    match node {
+        Node::Number(n) => *n,
+        Node::Variable(i) => values[*i],
+        Node::Negate(inner) => -eval(inner, values),
+        Node::Binary(left, op, right) => {
+            let (a, b) = (eval(left, values), eval(right, values));
+            match op {
+                Operator::Add => a + b,
+                Operator::Subtract => a - b,
+                Operator::Multiply => a * b,
+                Operator::Divide => a / b,
+            }
+        }
+        Node::Call(function, args) => {
+            let args = args.iter().map(|arg| eval(arg, values));
+            match function {
+                Function::Sum => args.sum(),
+                Function::Avg => {
+                    let count = args.len() as f64;
+                    args.sum::<f64>() / count
+                }
+                Function::Min => args.fold(f64::INFINITY, f64::min),
+                Function::Max => args.fold(f64::NEG_INFINITY, f64::max),
+            }
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                let exponent_sign = (c == '+' || c == '-') && text[..i].ends_with(['e', 'E']);
+                if !(c.is_ascii_alphanumeric() || c == '.' || exponent_sign) {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let number = &text[start..end];
+            tokens.push(Token::Number(
+                number
+                    .parse()
+                    .map_err(|_| format!("'{}' is not a number", number))?,
+            ));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Name(text[start..end].to_string()));
+        } else if "+-*/(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(n) => format!("number {}", n),
+        Token::Name(name) => format!("name '{}'", name),
+        Token::Symbol(c) => format!("'{}'", c),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    variables: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        if self.peek() == Some(&Token::Symbol(symbol)) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Symbol(c)) if c == symbol => Ok(()),
+            Some(token) => Err(format!("expected '{}', found {}", symbol, describe(&token))),
+            None => Err(format!("expected '{}' at the end", symbol)),
+        }
+    }
+
+    /// expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<Node, String> {
+        let mut node = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                Operator::Add
+            } else if self.eat('-') {
+                Operator::Subtract
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(Box::new(node), op, Box::new(self.term()?));
+        }
+    }
+
+    /// term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Node, String> {
+        let mut node = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                Operator::Multiply
+            } else if self.eat('/') {
+                Operator::Divide
+            } else {
+                return Ok(node);
+            };
+            node = Node::Binary(Box::new(node), op, Box::new(self.unary()?));
+        }
+    }
+
+    /// unary := '-' unary | primary
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.eat('-') {
+            return Ok(Node::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    /// primary := number | name | name '(' expression (',' expression)* ')' | '(' expression ')'
+    fn primary(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Node::Number(n)),
+            Some(Token::Symbol('(')) => {
+                let node = self.expression()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            Some(Token::Name(name)) if self.eat('(') => {
+                let function = match name.as_str() {
+                    "sum" => Function::Sum,
+                    "avg" => Function::Avg,
+                    "min" => Function::Min,
+                    "max" => Function::Max,
+                    _ => return Err(format!("unknown function '{}'", name)),
+                };
+                let mut args = vec![self.expression()?];
+                while self.eat(',') {
+                    args.push(self.expression()?);
+                }
+                self.expect(')')?;
+                Ok(Node::Call(function, args))
+            }
+            Some(Token::Name(name)) => {
+                let index = match self.variables.iter().position(|v| *v == name) {
+                    Some(index) => index,
+                    None => {
+                        self.variables.push(name);
+                        self.variables.len() - 1
+                    }
+                };
+                Ok(Node::Variable(index))
+            }
+            Some(token) => Err(format!("unexpected {}", describe(&token))),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_with(text: &str, values: &[f64]) -> f64 {
+        Expression::parse(text).unwrap().evaluate(values).unwrap()
+    }
+
+    #[test]
+    fn test_evaluate_expressions() {
+        let expr = Expression::parse("P1 + P2 + P3").unwrap();
+        assert_eq!(expr.variables(), ["P1", "P2", "P3"]);
+        assert_eq!(expr.evaluate(&[1.0, 2.0, 3.5]).unwrap(), 6.5);
+
+        assert_eq!(eval_with("V * I / 1000", &[230.0, 10.0]), 2.3);
+        assert_eq!(eval_with("2 + 3 * 4 - -1", &[]), 15.0);
+        assert_eq!(eval_with("(2 + 3) * 4", &[]), 20.0);
+        assert_eq!(eval_with("1.5e3 / 2", &[]), 750.0);
+        assert_eq!(eval_with("avg(a, b) - min(a, b, 0)", &[2.0, 4.0]), 3.0);
+        assert_eq!(eval_with("max(a, b) + sum(a)", &[2.0, 4.0]), 6.0);
+
+        // A name used twice is one input
+        let expr = Expression::parse("a * a + a").unwrap();
+        assert_eq!(expr.variables(), ["a"]);
+        assert_eq!(expr.evaluate(&[3.0]).unwrap(), 12.0);
+
+        assert!(Expression::parse("a / b")
+            .unwrap()
+            .evaluate(&[1.0, 0.0])
+            .is_err());
+    }
+
+    #[test]
+    fn test_reject_invalid_expressions() {
+        for text in [
+            "",
+            "a +",
+            "(a",
+            "a b",
+            "sqrt(a)",
+            "max()",
+            "a; rm -rf",
+            "1.2.3",
+        ] {
+            assert!(
+                matches!(Expression::parse(text), Err(SourceError::ParseError(_))),
+                "{:?} should be rejected",
+                text
+            );
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use super::{DataSource, Reading};
+use crate::config::FilterConfig;
+use crate::error::SourceError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use tracing::warn;
+
+/// Drops readings that can't be real measurements before they are sent.
+pub struct ValueFilter {
+    config: FilterConfig,
+    /// Value and time of the last reading that passed
+    last: Option<(f64, DateTime<Utc>)>,
+}
+
+impl ValueFilter {
+    pub fn new(config: Option<&FilterConfig>) -> Self {
+        Self {
+            config: config.cloned().unwrap_or_default(),
+            last: None,
+        }
+    }
+
+    /// `Rejected` with the reason when the reading must not be sent.
+    pub fn check(&mut self, reading: &Reading) -> Result<(), SourceError> {
+        let value = reading.value;
+        let reject = |reason: String| Err(SourceError::Rejected(reason));
+
+        if !value.is_finite() {
+            return reject(format!("{} is not a number", value));
+        }
+        if let Some(min) = self.config.min.filter(|min| value < *min) {
+            return reject(format!("{} is below the minimum {}", value, min));
+        }
+        if let Some(max) = self.config.max.filter(|max| value > *max) {
+            return reject(format!("{} is above the maximum {}", value, max));
+        }
+
+        // Measured against the last accepted reading, so the allowed change grows
+        // with time and a genuine step is let through eventually
+        if let (Some(max_rate), Some((last_value, last_at))) =
+            (self.config.max_rate_per_sec, self.last)
+        {
+            let secs = (reading.timestamp - last_at).num_milliseconds() as f64 / 1000.0;
+            if secs > 0.0 && (value - last_value).abs() / secs > max_rate {
+                return reject(format!(
+                    "{} changed from {} at more than {} per second",
+                    value, last_value, max_rate
+                ));
+            }
+        }
+
+        self.last = Some((value, reading.timestamp));
+        Ok(())
+    }
+}
+
+/// Drops the values a device writes when it has no measurement.
+///
+/// Wraps the source itself, so `ignore_values` are recognised before a counter or
+/// unit conversion turns them into something else.
+pub struct NoDataFilter {
+    inner: Box<dyn DataSource>,
+    ignore_values: Vec<f64>,
+    /// Applied by the inner source, so the values are compared scaled the same way
+    multiplier: f64,
+}
+
+impl NoDataFilter {
+    pub fn new(inner: Box<dyn DataSource>, ignore_values: &[f64], multiplier: f64) -> Self {
+        Self {
+            inner,
+            ignore_values: ignore_values.to_vec(),
+            multiplier,
+        }
+    }
+
+    /// The configured value `value` was read as, if it is one of them.
+    fn no_data_value(&self, value: f64) -> Option<f64> {
+        self.ignore_values.iter().copied().find(|ignored| {
+            let scaled = ignored * self.multiplier;
+            (value - scaled).abs() <= scaled.abs() * 1e-9
+        })
+    }
+}
+
+#[async_trait]
+impl DataSource for NoDataFilter {
+    async fn read_value(&self) -> Result<Reading, SourceError> {
+        let reading = self.inner.read_value().await?;
+        match self.no_data_value(reading.value) {
+            Some(ignored) => Err(SourceError::Rejected(format!(
+                "{} is a no-data value",
+                ignored
+            ))),
+            None => Ok(reading),
+        }
+    }
+
+    async fn read_values(&self) -> Result<Vec<Reading>, SourceError> {
+        let mut readings = self.inner.read_values().await?;
+        readings.retain(|reading| match self.no_data_value(reading.value) {
+            Some(ignored) => {
+                warn!(source = %self.source_id(), value = ignored, "Dropping no-data value");
+                false
+            }
+            None => true,
+        });
+        Ok(readings)
+    }
+
+    async fn commit(&self) -> Result<(), SourceError> {
+        self.inner.commit().await
+    }
+
+    fn source_id(&self) -> &str {
+        self.inner.source_id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(value: f64, secs: i64) -> Reading {
+        Reading {
+            value,
+            unit: "kW".to_string(),
+            timestamp: DateTime::<Utc>::UNIX_EPOCH + chrono::Duration::seconds(secs),
+            source_id: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_filter_rules() {
+        let mut filter = ValueFilter::new(Some(&FilterConfig {
+            min: Some(0.0),
+            max: Some(500.0),
+            max_rate_per_sec: Some(1.0),
+            ignore_values: Vec::new(),
+        }));
+
+        assert!(filter.check(&reading(100.0, 0)).is_ok());
+        assert!(filter.check(&reading(f64::NAN, 10)).is_err());
+        assert!(filter.check(&reading(-5.0, 10)).is_err());
+        assert!(filter.check(&reading(999999.0, 10)).is_err());
+
+        // +50 in 10 s is too fast, but fine 60 s after the last accepted reading
+        assert!(filter.check(&reading(150.0, 10)).is_err());
+        assert!(filter.check(&reading(150.0, 60)).is_ok());
+        assert!(filter.check(&reading(149.0, 61)).is_ok());
+
+        let mut unfiltered = ValueFilter::new(None);
+        assert!(unfiltered.check(&reading(-1e9, 0)).is_ok());
+        assert!(matches!(
+            unfiltered.check(&reading(f64::INFINITY, 0)),
+            Err(SourceError::Rejected(_))
+        ));
+    }
+
+    fn source(multiplier: f64, input_unit: &str, path: &std::path::Path) -> Box<dyn DataSource> {
+        let config: crate::config::SourceConfig = toml::from_str(&format!(
+            r#"
+            type = "json"
+            input_unit = "{input_unit}"
+            filter = {{ ignore_values = [999999, -1] }}
+            json = {{ path = "{path}", json_path = "$.power", unit = "MW", multiplier = {multiplier} }}
+            "#,
+            path = path.display(),
+        ))
+        .unwrap();
+        super::super::create_source(&config, std::time::Duration::from_secs(60)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_no_data_values_are_matched_before_scaling() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let scaled = source(0.001, "MW", file.path());
+        let converted = source(1.0, "W", file.path());
+
+        std::fs::write(file.path(), r#"{"power": 999999}"#).unwrap();
+        for source in [&scaled, &converted] {
+            assert!(matches!(
+                source.read_value().await,
+                Err(SourceError::Rejected(_))
+            ));
+            assert!(source.read_values().await.unwrap().is_empty());
+        }
+
+        std::fs::write(file.path(), r#"{"power": -1}"#).unwrap();
+        assert!(scaled.read_values().await.unwrap().is_empty());
+
+        std::fs::write(file.path(), r#"{"power": 1500000}"#).unwrap();
+        assert_eq!(scaled.read_value().await.unwrap().value, 1500.0);
+        assert_eq!(converted.read_value().await.unwrap().value, 1.5);
+    }
+}
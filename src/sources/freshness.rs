@@ -0,0 +1,96 @@
+use crate::error::SourceError;
+use chrono::{DateTime, Utc};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Staleness and repeat detection for sources that read a file another program writes.
+pub struct Freshness {
+    max_age: Option<chrono::Duration>,
+    suppress_unchanged: bool,
+    /// Value and version (row timestamp or file mtime) of the last reading produced
+    last: Mutex<Option<(f64, DateTime<Utc>)>>,
+}
+
+impl Freshness {
+    pub fn new(max_age_secs: Option<u64>, suppress_unchanged: bool) -> Self {
+        Self {
+            max_age: max_age_secs.map(|secs| chrono::Duration::seconds(secs as i64)),
+            suppress_unchanged,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// The file's modification time, or `Stale` when it hasn't been written within `max_age`.
+    pub async fn check_modified(&self, path: &Path) -> Result<DateTime<Utc>, SourceError> {
+        let modified: DateTime<Utc> = tokio::fs::metadata(path)
+            .await
+            .and_then(|m| m.modified())
+            .map_err(|e| SourceError::ReadError(e.to_string()))?
+            .into();
+
+        self.check(modified, || {
+            format!("{} has not been modified since", path.display())
+        })?;
+        Ok(modified)
+    }
+
+    /// `Stale` when a reading's own timestamp is older than `max_age`.
+    pub fn check_timestamp(&self, timestamp: DateTime<Utc>) -> Result<(), SourceError> {
+        self.check(timestamp, || "Latest reading is timestamped".to_string())
+    }
+
+    fn check(&self, at: DateTime<Utc>, what: impl FnOnce() -> String) -> Result<(), SourceError> {
+        match self.max_age {
+            Some(max_age) if Utc::now() - at > max_age => Err(SourceError::Stale(format!(
+                "{} {} (older than {} seconds)",
+                what(),
+                at.to_rfc3339(),
+                max_age.num_seconds()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// With `suppress_unchanged`, whether this reading repeats the previous one:
+    /// same value and same version, so nothing new was written since.
+    pub fn is_repeat(&self, value: f64, version: DateTime<Utc>) -> bool {
+        if !self.suppress_unchanged {
+            return false;
+        }
+
+        let mut last = self.last.lock().unwrap();
+        let repeat = *last == Some((value, version));
+        *last = Some((value, version));
+        repeat
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_age_and_repeats() {
+        let freshness = Freshness::new(Some(60), true);
+        let now = Utc::now();
+
+        assert!(freshness.check_timestamp(now).is_ok());
+        assert!(matches!(
+            freshness.check_timestamp(now - chrono::Duration::seconds(120)),
+            Err(SourceError::Stale(_))
+        ));
+
+        assert!(!freshness.is_repeat(1.0, now));
+        assert!(freshness.is_repeat(1.0, now));
+        // Same value written again later is a new reading
+        assert!(!freshness.is_repeat(1.0, now + chrono::Duration::seconds(1)));
+        assert!(!freshness.is_repeat(2.0, now + chrono::Duration::seconds(1)));
+
+        let disabled = Freshness::new(None, false);
+        assert!(disabled
+            .check_timestamp(now - chrono::Duration::days(1))
+            .is_ok());
+        assert!(!disabled.is_repeat(1.0, now));
+        assert!(!disabled.is_repeat(1.0, now));
+    }
+}
@@ -1,29 +1,60 @@
-use super::{DataSource, Reading};
-use crate::config::HttpSourceConfig;
+use super::{
+    extract_json_timestamp, CsvSource, DataSource, Expression, Freshness, JsonValueSelector,
+    Reading, TimestampParser, XmlPath,
+};
+use crate::config::{CsvSourceConfig, HttpResponseFormat, HttpSourceConfig};
 use crate::error::SourceError;
+use crate::transport::HttpAuth;
 use async_trait::async_trait;
-use chrono::Utc;
-use jsonpath_rust::JsonPathQuery;
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use reqwest::Method;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, SecondsFormat, Utc};
+use regex_lite::{Captures, Regex};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED,
+};
+use reqwest::{Method, StatusCode};
 use std::str::FromStr;
-use std::time::Duration;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
 
 pub struct HttpSource {
     client: reqwest::Client,
     url: String,
     method: Method,
-    json_path: String,
+    body: ResponseBody,
     unit: String,
     multiplier: f64,
+    timestamp_parser: TimestampParser,
     headers: HeaderMap,
+    query: Vec<(String, String)>,
+    body_template: Option<String>,
+    auth: Option<HttpAuth>,
+    placeholder: Regex,
+    freshness: Freshness,
+    /// The last full response, answered again when the server says it's unchanged
+    cache: Mutex<Option<CachedResponse>>,
+    /// No requests until then, after the server answered 429 with `Retry-After`
+    retry_at: Mutex<Option<Instant>>,
     source_id: String,
 }
 
+struct CachedResponse {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    /// After `multiplier`
+    value: f64,
+    timestamp: Option<DateTime<Utc>>,
+    /// `Last-Modified`, or when the response was received
+    changed_at: DateTime<Utc>,
+}
+
 impl HttpSource {
     pub fn new(config: &HttpSourceConfig) -> Result<Self, SourceError> {
-        let method = Method::from_str(&config.method.to_uppercase())
-            .map_err(|_| SourceError::ParseError(format!("Invalid HTTP method: {}", config.method)))?;
+        let method = Method::from_str(&config.method.to_uppercase()).map_err(|_| {
+            SourceError::ParseError(format!("Invalid HTTP method: {}", config.method))
+        })?;
 
         // Build headers
         let mut headers = HeaderMap::new();
@@ -34,92 +65,578 @@ impl HttpSource {
                 .map_err(|_| SourceError::ParseError(format!("Invalid header value: {}", value)))?;
             headers.insert(header_name, header_value);
         }
+        if config.body_template.is_some() && !headers.contains_key(CONTENT_TYPE) {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        }
 
         let client = reqwest::Client::builder()
             .timeout(Duration::from_secs(config.timeout_secs))
             .build()
             .map_err(|e| SourceError::HttpError(e.to_string()))?;
 
-        Ok(Self {
+        let source = Self {
             client,
             url: config.url.clone(),
             method,
-            json_path: config.json_path.clone(),
+            body: ResponseBody::new(config)?,
             unit: config.unit.clone(),
             multiplier: config.multiplier,
+            timestamp_parser: TimestampParser::new(
+                config.timestamp_format.as_deref(),
+                config.timezone.as_deref(),
+            )?,
             headers,
+            query: config
+                .query
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            body_template: config.body_template.clone(),
+            auth: config.auth.as_ref().map(HttpAuth::new),
+            placeholder: Regex::new(r"\{now(?:([+-])(\d+)([smhd]))?(?::([^{}]+))?\}").unwrap(),
+            freshness: Freshness::new(config.max_age_secs, config.suppress_unchanged),
+            cache: Mutex::new(None),
+            retry_at: Mutex::new(None),
             source_id: format!("http:{}", config.url),
-        })
+        };
+
+        // Catch placeholder mistakes at startup rather than on every poll
+        let now = Utc::now();
+        for (_, value) in &source.query {
+            source.render(value, now)?;
+        }
+        if let Some(body) = &source.body_template {
+            source.render(body, now)?;
+        }
+
+        Ok(source)
     }
 
-    fn extract_value(&self, json: &serde_json::Value) -> Result<f64, SourceError> {
-        let result = json
-            .clone()
-            .path(&self.json_path)
-            .map_err(|e| SourceError::JsonError(format!("Invalid JSONPath: {}", e)))?;
+    /// Replace `{now}`, `{now-5m}`, `{now+1h:%Y-%m-%d}` or `{now-1d:epoch}` with times
+    /// relative to `now`. Units are s, m, h and d; the format after `:` is `epoch`,
+    /// `epoch_ms` or a strftime pattern, RFC 3339 in UTC by default.
+    fn render(&self, template: &str, now: DateTime<Utc>) -> Result<String, SourceError> {
+        let mut error = None;
+        let rendered = self
+            .placeholder
+            .replace_all(template, |caps: &Captures| match render_time(caps, now) {
+                Ok(text) => text,
+                Err(e) => {
+                    error.get_or_insert(e);
+                    String::new()
+                }
+            })
+            .into_owned();
 
-        let value = match &result {
-            serde_json::Value::Array(arr) => arr.first().cloned(),
-            v => Some(v.clone()),
-        };
+        match error {
+            Some(e) => Err(e),
+            None => Ok(rendered),
+        }
+    }
+}
 
-        let value = value.ok_or_else(|| {
-            SourceError::ValueNotFound(format!("No value found at path: {}", self.json_path))
-        })?;
+/// How the value, and optionally its time, are found in the response body.
+enum ResponseBody {
+    Json {
+        value: JsonValueSelector,
+        timestamp_path: Option<String>,
+    },
+    /// Parsed like a CSV file; the source's multiplier is applied by `HttpSource`
+    Csv {
+        rows: Box<CsvSource>,
+        /// Whether rows carry their own time, rather than the time they were parsed
+        timestamped: bool,
+    },
+    Xml {
+        paths: Vec<XmlPath>,
+        expression: Option<Expression>,
+        timestamp_path: Option<XmlPath>,
+    },
+    Regex(Regex),
+    Number,
+}
 
-        match value {
-            serde_json::Value::Number(n) => n.as_f64().ok_or_else(|| {
-                SourceError::InvalidValueType(format!("Number {} cannot be converted to f64", n))
+impl ResponseBody {
+    fn new(config: &HttpSourceConfig) -> Result<Self, SourceError> {
+        match config.format {
+            HttpResponseFormat::Json => Ok(Self::Json {
+                value: JsonValueSelector::new(
+                    &config.json_path,
+                    config.expression.as_deref(),
+                    &config.variables,
+                )?,
+                timestamp_path: config.timestamp_path.clone(),
             }),
-            serde_json::Value::String(s) => s.trim().parse::<f64>().map_err(|_| {
-                SourceError::InvalidValueType(format!("String '{}' is not a valid number", s))
+            HttpResponseFormat::Csv => Ok(Self::Csv {
+                rows: Box::new(CsvSource::new(&CsvSourceConfig {
+                    path: "response".into(),
+                    value_field: config.value_field.clone(),
+                    expression: config.expression.clone(),
+                    variables: config.variables.clone(),
+                    unit: config.unit.clone(),
+                    read_last_row: true,
+                    delimiter: config.delimiter.clone(),
+                    skip_headers: 0,
+                    multiplier: 1.0,
+                    mode: Default::default(),
+                    cursor_file: None,
+                    timestamp_field: config.timestamp_path.clone(),
+                    timestamp_format: config.timestamp_format.clone(),
+                    timezone: config.timezone.clone(),
+                    max_age_secs: None,
+                    suppress_unchanged: false,
+                    file_pattern: None,
+                    newest_by: Default::default(),
+                })?),
+                timestamped: config.timestamp_path.is_some(),
             }),
-            other => Err(SourceError::InvalidValueType(format!(
-                "Expected number, got {:?}",
-                other
-            ))),
+            HttpResponseFormat::Xml => {
+                let (paths, expression) = match &config.expression {
+                    Some(expression) => {
+                        let expression = Expression::parse(expression)?;
+                        let paths = expression
+                            .variables()
+                            .iter()
+                            .map(|name| match config.variables.get(name) {
+                                Some(path) => XmlPath::parse(path),
+                                None => XmlPath::parse(&format!("//{}", name)),
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        (paths, Some(expression))
+                    }
+                    None => {
+                        let path = config.xml_path.as_deref().unwrap_or_default();
+                        (vec![XmlPath::parse(path)?], None)
+                    }
+                };
+                Ok(Self::Xml {
+                    paths,
+                    expression,
+                    timestamp_path: config
+                        .timestamp_path
+                        .as_deref()
+                        .map(XmlPath::parse)
+                        .transpose()?,
+                })
+            }
+            HttpResponseFormat::Regex => {
+                let pattern = config.pattern.as_deref().unwrap_or_default();
+                let regex = Regex::new(pattern).map_err(|e| {
+                    SourceError::ParseError(format!("Invalid pattern '{}': {}", pattern, e))
+                })?;
+                if regex.captures_len() < 2 {
+                    return Err(SourceError::ParseError(format!(
+                        "Pattern '{}' needs a capture group around the value",
+                        pattern
+                    )));
+                }
+                Ok(Self::Regex(regex))
+            }
+            HttpResponseFormat::Number => Ok(Self::Number),
         }
     }
 }
 
-#[async_trait]
-impl DataSource for HttpSource {
-    async fn read_value(&self) -> Result<Reading, SourceError> {
-        let request = self
+impl HttpSource {
+    /// The value before `multiplier`, and the measurement time when the response has one.
+    fn parse_body(&self, body: &str) -> Result<(f64, Option<DateTime<Utc>>), SourceError> {
+        match &self.body {
+            ResponseBody::Json {
+                value,
+                timestamp_path,
+            } => {
+                let json: serde_json::Value = serde_json::from_str(body)
+                    .map_err(|e| SourceError::JsonError(e.to_string()))?;
+                let timestamp = timestamp_path
+                    .as_deref()
+                    .map(|path| extract_json_timestamp(&json, path, &self.timestamp_parser))
+                    .transpose()?;
+                Ok((value.extract(&json)?, timestamp))
+            }
+            ResponseBody::Csv { rows, timestamped } => {
+                let reading = rows.parse_value(body)?;
+                Ok((reading.value, timestamped.then_some(reading.timestamp)))
+            }
+            ResponseBody::Xml {
+                paths,
+                expression,
+                timestamp_path,
+            } => {
+                let document = roxmltree::Document::parse(body)
+                    .map_err(|e| SourceError::XmlError(e.to_string()))?;
+                let values = paths
+                    .iter()
+                    .map(|path| parse_number(&path.select(&document)?))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let value = match expression {
+                    Some(expression) => expression.evaluate(&values)?,
+                    None => values[0],
+                };
+                let timestamp = timestamp_path
+                    .as_ref()
+                    .map(|path| self.timestamp_parser.parse(&path.select(&document)?))
+                    .transpose()?;
+                Ok((value, timestamp))
+            }
+            ResponseBody::Regex(regex) => {
+                let captures = regex.captures(body).ok_or_else(|| {
+                    SourceError::ValueNotFound(format!("Pattern '{}' does not match", regex))
+                })?;
+                let value = captures
+                    .name("value")
+                    .or_else(|| captures.get(1))
+                    .ok_or_else(|| {
+                        SourceError::ValueNotFound("The value group did not match".to_string())
+                    })?;
+                let timestamp = captures
+                    .name("timestamp")
+                    .map(|m| self.timestamp_parser.parse(m.as_str()))
+                    .transpose()?;
+                Ok((parse_number(value.as_str())?, timestamp))
+            }
+            ResponseBody::Number => Ok((parse_number(body)?, None)),
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Result<f64, SourceError> {
+    text.trim()
+        .parse()
+        .map_err(|_| SourceError::InvalidValueType(format!("'{}' is not a number", text.trim())))
+}
+
+fn render_time(caps: &Captures, now: DateTime<Utc>) -> Result<String, SourceError> {
+    let invalid = || SourceError::ParseError(format!("Invalid time placeholder: {}", &caps[0]));
+
+    let mut at = now;
+    if let (Some(sign), Some(amount), Some(unit)) = (caps.get(1), caps.get(2), caps.get(3)) {
+        let amount: i64 = amount.as_str().parse().map_err(|_| invalid())?;
+        let secs = match unit.as_str() {
+            "s" => Some(amount),
+            "m" => amount.checked_mul(60),
+            "h" => amount.checked_mul(3600),
+            _ => amount.checked_mul(86400),
+        };
+        let offset = secs
+            .and_then(chrono::Duration::try_seconds)
+            .ok_or_else(invalid)?;
+        at = match sign.as_str() {
+            "-" => now.checked_sub_signed(offset),
+            _ => now.checked_add_signed(offset),
+        }
+        .ok_or_else(invalid)?;
+    }
+
+    match caps.get(4).map(|m| m.as_str()) {
+        None => Ok(at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        Some("epoch") => Ok(at.timestamp().to_string()),
+        Some("epoch_ms") => Ok(at.timestamp_millis().to_string()),
+        Some(format) => {
+            if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+                return Err(invalid());
+            }
+            Ok(at.format(format).to_string())
+        }
+    }
+}
+
+impl HttpSource {
+    /// The current reading and its version: the body's timestamp, or when the
+    /// response last changed.
+    async fn fetch(&self) -> Result<(Reading, DateTime<Utc>), SourceError> {
+        if let Some(at) = *self.retry_at.lock().unwrap() {
+            let wait = at.saturating_duration_since(Instant::now());
+            if !wait.is_zero() {
+                return Err(SourceError::RateLimited(Some(wait.as_secs().max(1))));
+            }
+        }
+
+        let now = Utc::now();
+        let query = self
+            .query
+            .iter()
+            .map(|(k, v)| Ok((k.as_str(), self.render(v, now)?)))
+            .collect::<Result<Vec<_>, SourceError>>()?;
+
+        let mut request = self
             .client
             .request(self.method.clone(), &self.url)
-            .headers(self.headers.clone());
+            .headers(self.headers.clone())
+            .query(&query);
+        if let Some(body) = &self.body_template {
+            request = request.body(self.render(body, now)?);
+        }
+        // Only reads can be revalidated; a POST is answered fresh every time
+        if matches!(self.method, Method::GET | Method::HEAD) {
+            if let Some(cached) = self.cache.lock().unwrap().as_ref() {
+                if let Some(etag) = &cached.etag {
+                    request = request.header(IF_NONE_MATCH, etag.clone());
+                }
+                if let Some(last_modified) = &cached.last_modified {
+                    request = request.header(IF_MODIFIED_SINCE, last_modified.clone());
+                }
+            }
+        }
+        if let Some(auth) = &self.auth {
+            request = auth
+                .apply(&self.client, request)
+                .await
+                .map_err(|e| SourceError::HttpError(e.to_string()))?;
+        }
 
         let response = request
             .send()
             .await
             .map_err(|e| SourceError::HttpError(e.to_string()))?;
 
-        if !response.status().is_success() {
-            return Err(SourceError::HttpError(format!(
-                "HTTP {} - {}",
-                response.status(),
-                response.status().canonical_reason().unwrap_or("Unknown error")
-            )));
-        }
+        let status = response.status();
+        let (value, timestamp, changed_at) = if status == StatusCode::NOT_MODIFIED {
+            let cache = self.cache.lock().unwrap();
+            let cached = cache.as_ref().ok_or_else(|| SourceError::HttpStatus {
+                status: status.as_u16(),
+                message: "Not Modified without an earlier response".to_string(),
+            })?;
+            debug!(source = %self.source_id, "Response not modified");
+            (cached.value, cached.timestamp, cached.changed_at)
+        } else if status == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = crate::transport::retry_after_header(&response);
+            if let Some(secs) = retry_after {
+                warn!(source = %self.source_id, retry_after = secs, "Rate limited by the source");
+                *self.retry_at.lock().unwrap() = Some(Instant::now() + Duration::from_secs(secs));
+            }
+            return Err(SourceError::RateLimited(retry_after));
+        } else if !status.is_success() {
+            if status == StatusCode::UNAUTHORIZED {
+                if let Some(auth) = &self.auth {
+                    auth.invalidate().await;
+                }
+            }
+            return Err(SourceError::HttpStatus {
+                status: status.as_u16(),
+                message: status
+                    .canonical_reason()
+                    .unwrap_or("Unknown error")
+                    .to_string(),
+            });
+        } else {
+            let etag = response.headers().get(ETAG).cloned();
+            let last_modified = response.headers().get(LAST_MODIFIED).cloned();
+            let changed_at = last_modified
+                .as_ref()
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| DateTime::parse_from_rfc2822(v).ok())
+                .map_or(now, |at| at.with_timezone(&Utc));
 
-        let json: serde_json::Value = response
-            .json()
-            .await
-            .map_err(|e| SourceError::JsonError(e.to_string()))?;
+            let body = response
+                .text()
+                .await
+                .map_err(|e| SourceError::HttpError(e.to_string()))?;
+            let (raw_value, timestamp) = self.parse_body(&body)?;
+            let value = raw_value * self.multiplier;
+
+            *self.cache.lock().unwrap() = Some(CachedResponse {
+                etag,
+                last_modified,
+                value,
+                timestamp,
+                changed_at,
+            });
+            (value, timestamp, changed_at)
+        };
 
-        let raw_value = self.extract_value(&json)?;
-        let value = raw_value * self.multiplier;
+        let version = timestamp.unwrap_or(changed_at);
+        self.freshness.check_timestamp(version)?;
 
-        Ok(Reading {
+        let reading = Reading {
             value,
             unit: self.unit.clone(),
-            timestamp: Utc::now(),
+            timestamp: timestamp.unwrap_or_else(Utc::now),
             source_id: self.source_id.clone(),
-        })
+        };
+        Ok((reading, version))
+    }
+}
+
+#[async_trait]
+impl DataSource for HttpSource {
+    async fn read_value(&self) -> Result<Reading, SourceError> {
+        Ok(self.fetch().await?.0)
+    }
+
+    async fn read_values(&self) -> Result<Vec<Reading>, SourceError> {
+        let (reading, version) = self.fetch().await?;
+        if self.freshness.is_repeat(reading.value, version) {
+            return Ok(Vec::new());
+        }
+        Ok(vec![reading])
     }
 
     fn source_id(&self) -> &str {
         &self.source_id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::MockServer;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+
+    fn config() -> HttpSourceConfig {
+        HttpSourceConfig {
+            url: "http://127.0.0.1:1/api".to_string(),
+            method: "POST".to_string(),
+            format: HttpResponseFormat::Json,
+            json_path: "$.power".to_string(),
+            value_field: String::new(),
+            delimiter: ",".to_string(),
+            xml_path: None,
+            pattern: None,
+            expression: None,
+            variables: HashMap::new(),
+            unit: "kW".to_string(),
+            headers: HashMap::new(),
+            query: HashMap::new(),
+            body_template: None,
+            auth: None,
+            timeout_secs: 5,
+            multiplier: 1.0,
+            timestamp_path: None,
+            timestamp_format: None,
+            timezone: None,
+            max_age_secs: None,
+            suppress_unchanged: false,
+        }
+    }
+
+    fn http_source(
+        query: &[(&str, &str)],
+        body_template: Option<&str>,
+    ) -> Result<HttpSource, SourceError> {
+        HttpSource::new(&HttpSourceConfig {
+            query: query
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            body_template: body_template.map(str::to_string),
+            ..config()
+        })
+    }
+
+    #[test]
+    fn test_render_time_placeholders() {
+        let source = http_source(
+            &[("from", "{now-5m}")],
+            Some(r#"{"day": "{now:%Y-%m-%d}"}"#),
+        )
+        .unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 10, 15, 0).unwrap();
+
+        assert_eq!(source.render("{now}", now).unwrap(), "2024-03-01T10:15:00Z");
+        assert_eq!(
+            source.render("from={now-5m}&to={now}", now).unwrap(),
+            "from=2024-03-01T10:10:00Z&to=2024-03-01T10:15:00Z"
+        );
+        assert_eq!(source.render("{now-1d:epoch}", now).unwrap(), "1709201700");
+        assert_eq!(
+            source
+                .render(r#"{"day": "{now+1h:%Y-%m-%d %H}"}"#, now)
+                .unwrap(),
+            r#"{"day": "2024-03-01 11"}"#
+        );
+
+        assert!(http_source(&[("from", "{now:%Q}")], None).is_err());
+        assert!(http_source(&[], Some("{now-99999999999999d}")).is_err());
+    }
+
+    #[test]
+    fn test_response_formats() {
+        let value = |config: HttpSourceConfig, body: &str| {
+            HttpSource::new(&config)
+                .unwrap()
+                .parse_body(body)
+                .map(|(v, _)| v)
+        };
+
+        let mut csv = config();
+        csv.format = HttpResponseFormat::Csv;
+        csv.value_field = "P".to_string();
+        csv.delimiter = ";".to_string();
+        assert_eq!(value(csv, "time;P\n10:00;1.5\n10:05;2.5\n").unwrap(), 2.5);
+
+        let mut xml = config();
+        xml.format = HttpResponseFormat::Xml;
+        xml.expression = Some("Pac / 1000".to_string());
+        xml.variables
+            .insert("Pac".to_string(), "//Value[@name='Pac']".to_string());
+        let body = r#"<Data><Value name="Pac">2500</Value></Data>"#;
+        assert_eq!(value(xml, body).unwrap(), 2.5);
+
+        let mut regex = config();
+        regex.format = HttpResponseFormat::Regex;
+        regex.pattern = Some(r"Power:\s*([\d.]+) kW".to_string());
+        assert_eq!(value(regex.clone(), "<b>Power: 3.75 kW</b>").unwrap(), 3.75);
+        assert!(matches!(
+            value(regex, "offline"),
+            Err(SourceError::ValueNotFound(_))
+        ));
+
+        let mut number = config();
+        number.format = HttpResponseFormat::Number;
+        assert_eq!(value(number.clone(), " 42.5\n").unwrap(), 42.5);
+        assert!(value(number, "n/a").is_err());
+    }
+
+    #[test]
+    fn test_csv_timestamp_comes_from_a_column() {
+        let mut csv = config();
+        csv.format = HttpResponseFormat::Csv;
+        csv.value_field = "P".to_string();
+        let body = "time,P\n2024-03-01T10:00:00Z,1.5\n";
+
+        let (_, timestamp) = HttpSource::new(&csv).unwrap().parse_body(body).unwrap();
+        assert_eq!(timestamp, None);
+
+        csv.timestamp_path = Some("time".to_string());
+        let (_, timestamp) = HttpSource::new(&csv).unwrap().parse_body(body).unwrap();
+        assert_eq!(
+            timestamp,
+            Some(Utc.with_ymd_and_hms(2024, 3, 1, 10, 0, 0).unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_conditional_requests_and_rate_limit() {
+        let server = MockServer::with_responses(vec![
+            "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nConnection: close\r\nContent-Length: 14\r\n\r\n{\"power\": 2.5}",
+            "HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n",
+            "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 120\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+        ])
+        .await;
+
+        let mut config = config();
+        config.url = format!("{}/api", server.url);
+        config.method = "GET".to_string();
+        config.suppress_unchanged = true;
+        let source = HttpSource::new(&config).unwrap();
+
+        assert_eq!(source.read_values().await.unwrap()[0].value, 2.5);
+        // Not modified: the cached value, which isn't sent twice
+        assert!(source.read_values().await.unwrap().is_empty());
+        assert!(matches!(
+            source.read_value().await,
+            Err(SourceError::RateLimited(Some(120)))
+        ));
+        // Backing off without asking again
+        assert!(matches!(
+            source.read_value().await,
+            Err(SourceError::RateLimited(Some(_)))
+        ));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].header("if-none-match"), None);
+        assert_eq!(requests[1].header("if-none-match"), Some("\"v1\""));
+    }
+}
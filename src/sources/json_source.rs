@@ -1,16 +1,20 @@
-use super::{DataSource, Reading};
+use super::{
+    extract_json_timestamp, DataSource, Freshness, JsonValueSelector, Reading, TimestampParser,
+};
 use crate::config::JsonSourceConfig;
 use crate::error::SourceError;
 use async_trait::async_trait;
-use chrono::Utc;
-use jsonpath_rust::JsonPathQuery;
+use chrono::{DateTime, Utc};
 use std::path::PathBuf;
 
 pub struct JsonSource {
     path: PathBuf,
-    json_path: String,
+    value: JsonValueSelector,
     unit: String,
     multiplier: f64,
+    timestamp_path: Option<String>,
+    timestamp_parser: TimestampParser,
+    freshness: Freshness,
     source_id: String,
 }
 
@@ -18,53 +22,31 @@ impl JsonSource {
     pub fn new(config: &JsonSourceConfig) -> Result<Self, SourceError> {
         Ok(Self {
             path: config.path.clone(),
-            json_path: config.json_path.clone(),
+            value: JsonValueSelector::new(
+                &config.json_path,
+                config.expression.as_deref(),
+                &config.variables,
+            )?,
             unit: config.unit.clone(),
             multiplier: config.multiplier,
+            timestamp_path: config.timestamp_path.clone(),
+            timestamp_parser: TimestampParser::new(
+                config.timestamp_format.as_deref(),
+                config.timezone.as_deref(),
+            )?,
+            freshness: Freshness::new(config.max_age_secs, config.suppress_unchanged),
             source_id: format!("json:{}", config.path.display()),
         })
     }
 
-    fn extract_value(&self, json: &serde_json::Value) -> Result<f64, SourceError> {
-        let result = json
-            .clone()
-            .path(&self.json_path)
-            .map_err(|e| SourceError::JsonError(format!("Invalid JSONPath: {}", e)))?;
-
-        // The result is a Value that could be an array or single value
-        let value = match &result {
-            serde_json::Value::Array(arr) => arr.first().cloned(),
-            v => Some(v.clone()),
-        };
-
-        let value = value.ok_or_else(|| {
-            SourceError::ValueNotFound(format!("No value found at path: {}", self.json_path))
-        })?;
-
-        match value {
-            serde_json::Value::Number(n) => n.as_f64().ok_or_else(|| {
-                SourceError::InvalidValueType(format!("Number {} cannot be converted to f64", n))
-            }),
-            serde_json::Value::String(s) => s.trim().parse::<f64>().map_err(|_| {
-                SourceError::InvalidValueType(format!("String '{}' is not a valid number", s))
-            }),
-            other => Err(SourceError::InvalidValueType(format!(
-                "Expected number, got {:?}",
-                other
-            ))),
-        }
-    }
-}
-
-#[async_trait]
-impl DataSource for JsonSource {
-    async fn read_value(&self) -> Result<Reading, SourceError> {
+    /// The current reading, along with the file's modification time.
+    async fn read_latest(&self) -> Result<(Reading, DateTime<Utc>), SourceError> {
         if !self.path.exists() {
-            return Err(SourceError::FileNotFound(
-                self.path.display().to_string(),
-            ));
+            return Err(SourceError::FileNotFound(self.path.display().to_string()));
         }
 
+        let modified = self.freshness.check_modified(&self.path).await?;
+
         let content = tokio::fs::read_to_string(&self.path)
             .await
             .map_err(|e| SourceError::ReadError(e.to_string()))?;
@@ -72,15 +54,43 @@ impl DataSource for JsonSource {
         let json: serde_json::Value =
             serde_json::from_str(&content).map_err(|e| SourceError::JsonError(e.to_string()))?;
 
-        let raw_value = self.extract_value(&json)?;
+        let raw_value = self.value.extract(&json)?;
         let value = raw_value * self.multiplier;
+        let timestamp = match &self.timestamp_path {
+            Some(path) => {
+                let timestamp = extract_json_timestamp(&json, path, &self.timestamp_parser)?;
+                self.freshness.check_timestamp(timestamp)?;
+                timestamp
+            }
+            None => Utc::now(),
+        };
 
-        Ok(Reading {
+        let reading = Reading {
             value,
             unit: self.unit.clone(),
-            timestamp: Utc::now(),
+            timestamp,
             source_id: self.source_id.clone(),
-        })
+        };
+        Ok((reading, modified))
+    }
+}
+
+#[async_trait]
+impl DataSource for JsonSource {
+    async fn read_value(&self) -> Result<Reading, SourceError> {
+        Ok(self.read_latest().await?.0)
+    }
+
+    async fn read_values(&self) -> Result<Vec<Reading>, SourceError> {
+        let (reading, modified) = self.read_latest().await?;
+        let version = match self.timestamp_path {
+            Some(_) => reading.timestamp,
+            None => modified,
+        };
+        if self.freshness.is_repeat(reading.value, version) {
+            return Ok(Vec::new());
+        }
+        Ok(vec![reading])
     }
 
     fn source_id(&self) -> &str {
@@ -91,6 +101,7 @@ impl DataSource for JsonSource {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -111,6 +122,13 @@ mod tests {
             json_path: "$.power".to_string(),
             unit: "kW".to_string(),
             multiplier: 1.0,
+            expression: None,
+            variables: HashMap::new(),
+            timestamp_path: None,
+            timestamp_format: None,
+            timezone: None,
+            max_age_secs: None,
+            suppress_unchanged: false,
         };
 
         let source = JsonSource::new(&config).unwrap();
@@ -139,6 +157,13 @@ mod tests {
             json_path: "$.meters[0].reading".to_string(),
             unit: "MW".to_string(),
             multiplier: 1.0,
+            expression: None,
+            variables: HashMap::new(),
+            timestamp_path: None,
+            timestamp_format: None,
+            timezone: None,
+            max_age_secs: None,
+            suppress_unchanged: false,
         };
 
         let source = JsonSource::new(&config).unwrap();
@@ -164,6 +189,13 @@ mod tests {
             json_path: "$.power_kw".to_string(),
             unit: "MW".to_string(),
             multiplier: 0.001,
+            expression: None,
+            variables: HashMap::new(),
+            timestamp_path: None,
+            timestamp_format: None,
+            timezone: None,
+            max_age_secs: None,
+            suppress_unchanged: false,
         };
 
         let source = JsonSource::new(&config).unwrap();
@@ -173,4 +205,61 @@ mod tests {
         assert!((reading.value - 1.0).abs() < 0.0001);
         assert_eq!(reading.unit, "MW");
     }
+
+    #[tokio::test]
+    async fn test_json_source_timestamp_path() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(file, r#"{{"power": 5.0, "ts": 1709288100}}"#).unwrap();
+
+        let config = JsonSourceConfig {
+            path: file.path().to_path_buf(),
+            json_path: "$.power".to_string(),
+            unit: "kW".to_string(),
+            multiplier: 1.0,
+            expression: None,
+            variables: HashMap::new(),
+            timestamp_path: Some("$.ts".to_string()),
+            timestamp_format: Some("epoch".to_string()),
+            timezone: None,
+            max_age_secs: None,
+            suppress_unchanged: false,
+        };
+
+        let source = JsonSource::new(&config).unwrap();
+        let reading = source.read_value().await.unwrap();
+
+        assert_eq!(reading.timestamp.to_rfc3339(), "2024-03-01T10:15:00+00:00");
+    }
+
+    #[tokio::test]
+    async fn test_json_source_expression() {
+        let mut file = NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"voltage": 230.0, "meters": [{{"current": 10.0}}, {{"current": 4.0}}]}}"#
+        )
+        .unwrap();
+
+        let config = JsonSourceConfig {
+            path: file.path().to_path_buf(),
+            json_path: String::new(),
+            unit: "kW".to_string(),
+            multiplier: 1.0,
+            expression: Some("voltage * (I1 + I2) / 1000".to_string()),
+            variables: HashMap::from([
+                ("I1".to_string(), "$.meters[0].current".to_string()),
+                ("I2".to_string(), "$.meters[1].current".to_string()),
+            ]),
+            timestamp_path: None,
+            timestamp_format: None,
+            timezone: None,
+            max_age_secs: None,
+            suppress_unchanged: false,
+        };
+
+        let source = JsonSource::new(&config).unwrap();
+        let reading = source.read_value().await.unwrap();
+
+        assert!((reading.value - 3.22).abs() < 1e-9);
+    }
 }
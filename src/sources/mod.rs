@@ -1,17 +1,40 @@
+pub mod convert;
+pub mod counter;
 pub mod csv_source;
+pub mod expression;
+pub mod filter;
+pub mod freshness;
 pub mod http_source;
 pub mod json_source;
+pub mod modbus_source;
+pub mod mqtt_source;
+pub mod state;
+pub mod timestamp;
+pub mod xml_path;
 
+pub use convert::UnitConversion;
+pub use counter::EnergyCounter;
 pub use csv_source::CsvSource;
+pub use expression::Expression;
+pub use filter::{NoDataFilter, ValueFilter};
+pub use freshness::Freshness;
 pub use http_source::HttpSource;
 pub use json_source::JsonSource;
+pub use modbus_source::ModbusSource;
+pub use mqtt_source::MqttSource;
+pub use timestamp::TimestampParser;
+pub use xml_path::XmlPath;
 
-use crate::config::{SourceConfig, SourceType};
+use crate::config::{CsvReadMode, Quantity, SourceConfig, SourceType, Unit};
 use crate::error::SourceError;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use jsonpath_rust::JsonPathQuery;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Reading {
     pub value: f64,
     pub unit: String,
@@ -22,31 +45,195 @@ pub struct Reading {
 #[async_trait]
 pub trait DataSource: Send + Sync {
     async fn read_value(&self) -> Result<Reading, SourceError>;
+
+    /// All readings produced since the previous poll, oldest first. May be empty.
+    ///
+    /// Sources that only know their current value return a single reading.
+    async fn read_values(&self) -> Result<Vec<Reading>, SourceError> {
+        Ok(vec![self.read_value().await?])
+    }
+
+    /// Called once the readings returned by the last `read_values` have been handed
+    /// over for delivery, so a source that keeps its position between runs only
+    /// saves it past readings that can no longer be lost.
+    async fn commit(&self) -> Result<(), SourceError> {
+        Ok(())
+    }
+
     fn source_id(&self) -> &str;
 }
 
-pub fn create_source(config: &SourceConfig) -> Result<Box<dyn DataSource>, SourceError> {
-    match config.source_type {
-        SourceType::Csv => {
-            let csv_config = config
-                .csv
-                .as_ref()
-                .ok_or_else(|| SourceError::ParseError("Missing CSV configuration".to_string()))?;
-            Ok(Box::new(CsvSource::new(csv_config)?))
+/// Extract a single number from a JSON document using a JSONPath expression.
+///
+/// Shared by every source that reads JSON. Numeric strings are accepted; when the
+/// path matches several values the first one is used.
+pub fn extract_json_value(json: &serde_json::Value, json_path: &str) -> Result<f64, SourceError> {
+    match select_json_value(json, json_path)? {
+        serde_json::Value::Number(n) => n.as_f64().ok_or_else(|| {
+            SourceError::InvalidValueType(format!("Number {} cannot be converted to f64", n))
+        }),
+        serde_json::Value::String(s) => s.trim().parse::<f64>().map_err(|_| {
+            SourceError::InvalidValueType(format!("String '{}' is not a valid number", s))
+        }),
+        other => Err(SourceError::InvalidValueType(format!(
+            "Expected number, got {:?}",
+            other
+        ))),
+    }
+}
+
+/// How a JSON source gets its value: one JSONPath, or an expression over several.
+pub struct JsonValueSelector {
+    paths: Vec<String>,
+    expression: Option<Expression>,
+}
+
+impl JsonValueSelector {
+    /// Names in `expression` are looked up in `variables`, and otherwise read as top-level fields.
+    pub fn new(
+        json_path: &str,
+        expression: Option<&str>,
+        variables: &HashMap<String, String>,
+    ) -> Result<Self, SourceError> {
+        let Some(expression) = expression else {
+            return Ok(Self {
+                paths: vec![json_path.to_string()],
+                expression: None,
+            });
+        };
+
+        let expression = Expression::parse(expression)?;
+        let paths = expression
+            .variables()
+            .iter()
+            .map(|name| {
+                variables
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_else(|| format!("$.{}", name))
+            })
+            .collect();
+
+        Ok(Self {
+            paths,
+            expression: Some(expression),
+        })
+    }
+
+    pub fn extract(&self, json: &serde_json::Value) -> Result<f64, SourceError> {
+        let values = self
+            .paths
+            .iter()
+            .map(|path| extract_json_value(json, path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match &self.expression {
+            Some(expression) => expression.evaluate(&values),
+            None => Ok(values[0]),
         }
-        SourceType::Json => {
-            let json_config = config
-                .json
-                .as_ref()
-                .ok_or_else(|| SourceError::ParseError("Missing JSON configuration".to_string()))?;
-            Ok(Box::new(JsonSource::new(json_config)?))
+    }
+}
+
+/// Extract the measurement time from a JSON document using a JSONPath expression.
+pub fn extract_json_timestamp(
+    json: &serde_json::Value,
+    json_path: &str,
+    parser: &TimestampParser,
+) -> Result<DateTime<Utc>, SourceError> {
+    parser.parse_json(&select_json_value(json, json_path)?)
+}
+
+/// The first value matched by a JSONPath expression.
+fn select_json_value(
+    json: &serde_json::Value,
+    json_path: &str,
+) -> Result<serde_json::Value, SourceError> {
+    let result = json
+        .clone()
+        .path(json_path)
+        .map_err(|e| SourceError::InvalidPath(format!("Invalid JSONPath: {}", e)))?;
+
+    // The result is a Value that could be an array or single value
+    let value = match result {
+        serde_json::Value::Array(arr) => arr.into_iter().next(),
+        v => Some(v),
+    };
+
+    value
+        .ok_or_else(|| SourceError::ValueNotFound(format!("No value found at path: {}", json_path)))
+}
+
+pub fn create_source(
+    config: &SourceConfig,
+    polling_interval: Duration,
+) -> Result<Box<dyn DataSource>, SourceError> {
+    let mut source: Box<dyn DataSource> =
+        match config.source_type {
+            SourceType::Csv => {
+                let csv_config = config.csv.as_ref().ok_or_else(|| {
+                    SourceError::ParseError("Missing CSV configuration".to_string())
+                })?;
+                let mut csv_config = csv_config.clone();
+                if csv_config.mode == CsvReadMode::Incremental && csv_config.cursor_file.is_none() {
+                    csv_config.cursor_file =
+                        Some(state::default_state_path(&config.name, "cursor"));
+                }
+                Box::new(CsvSource::new(&csv_config)?)
+            }
+            SourceType::Json => {
+                let json_config = config.json.as_ref().ok_or_else(|| {
+                    SourceError::ParseError("Missing JSON configuration".to_string())
+                })?;
+                Box::new(JsonSource::new(json_config)?)
+            }
+            SourceType::Http => {
+                let http_config = config.http.as_ref().ok_or_else(|| {
+                    SourceError::ParseError("Missing HTTP configuration".to_string())
+                })?;
+                Box::new(HttpSource::new(http_config)?)
+            }
+            SourceType::Modbus => {
+                let modbus_config = config.modbus.as_ref().ok_or_else(|| {
+                    SourceError::ParseError("Missing Modbus configuration".to_string())
+                })?;
+                Box::new(ModbusSource::new(modbus_config)?)
+            }
+            SourceType::Mqtt => {
+                let mqtt_config = config.mqtt.as_ref().ok_or_else(|| {
+                    SourceError::ParseError("Missing MQTT configuration".to_string())
+                })?;
+                Box::new(MqttSource::new(mqtt_config, polling_interval)?)
+            }
+        };
+
+    if let Some(filter) = config.filter.as_ref() {
+        if !filter.ignore_values.is_empty() {
+            let multiplier = config.multiplier().unwrap_or(1.0);
+            source = Box::new(NoDataFilter::new(source, &filter.ignore_values, multiplier));
         }
-        SourceType::Http => {
-            let http_config = config
-                .http
-                .as_ref()
-                .ok_or_else(|| SourceError::ParseError("Missing HTTP configuration".to_string()))?;
-            Ok(Box::new(HttpSource::new(http_config)?))
+    }
+
+    let mut input_unit = config.input_unit;
+    if let Some(counter) = &config.counter {
+        let energy_unit = input_unit
+            .filter(|unit| unit.quantity() == Quantity::Energy)
+            .ok_or_else(|| {
+                SourceError::ParseError("Counter requires an energy input_unit".to_string())
+            })?;
+        let state_file = counter
+            .state_file
+            .clone()
+            .unwrap_or_else(|| state::default_state_path(&config.name, "counter"));
+        source = Box::new(EnergyCounter::new(source, counter, energy_unit, state_file));
+        input_unit = Some(energy_unit.power());
+    }
+
+    match input_unit {
+        Some(input_unit) => {
+            let unit = config.unit().unwrap_or_default();
+            let target = unit.parse::<Unit>().map_err(SourceError::ParseError)?;
+            Ok(Box::new(UnitConversion::new(source, input_unit, target)?))
         }
+        None => Ok(source),
     }
 }
@@ -0,0 +1,360 @@
+use super::{DataSource, Reading};
+use crate::config::{ModbusDataType, ModbusRegisterType, ModbusSourceConfig, ModbusWordOrder};
+use crate::error::SourceError;
+use async_trait::async_trait;
+use chrono::Utc;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+const FC_READ_HOLDING_REGISTERS: u8 = 0x03;
+const FC_READ_INPUT_REGISTERS: u8 = 0x04;
+const MBAP_HEADER_LEN: usize = 7;
+
+pub struct ModbusSource {
+    address: String,
+    unit_id: u8,
+    register: u16,
+    register_type: ModbusRegisterType,
+    data_type: ModbusDataType,
+    word_order: ModbusWordOrder,
+    unit: String,
+    multiplier: f64,
+    timeout: Duration,
+    // Kept open between polls, re-established after any error
+    connection: Mutex<Option<TcpStream>>,
+    transaction_id: AtomicU16,
+    source_id: String,
+}
+
+impl ModbusSource {
+    pub fn new(config: &ModbusSourceConfig) -> Result<Self, SourceError> {
+        let address = format!("{}:{}", config.host, config.port);
+
+        Ok(Self {
+            source_id: format!("modbus:{}/{}/{}", address, config.unit_id, config.address),
+            address,
+            unit_id: config.unit_id,
+            register: config.address,
+            register_type: config.register_type,
+            data_type: config.data_type,
+            word_order: config.word_order,
+            unit: config.unit.clone(),
+            multiplier: config.multiplier,
+            timeout: Duration::from_secs(config.timeout_secs),
+            connection: Mutex::new(None),
+            transaction_id: AtomicU16::new(0),
+        })
+    }
+
+    async fn read_registers(&self) -> Result<Vec<u16>, SourceError> {
+        let mut connection = self.connection.lock().await;
+
+        if connection.is_none() {
+            let stream = tokio::time::timeout(self.timeout, TcpStream::connect(&self.address))
+                .await
+                .map_err(|_| {
+                    SourceError::ModbusError(format!("Connection to {} timed out", self.address))
+                })?
+                .map_err(|e| {
+                    SourceError::ModbusError(format!(
+                        "Connection to {} failed: {}",
+                        self.address, e
+                    ))
+                })?;
+            *connection = Some(stream);
+        }
+
+        let stream = connection.as_mut().expect("connection established above");
+        let result = tokio::time::timeout(self.timeout, self.transact(stream))
+            .await
+            .unwrap_or_else(|_| Err(SourceError::ModbusError("Request timed out".to_string())));
+
+        if result.is_err() {
+            // The stream may be out of sync with the device, start over next time
+            *connection = None;
+        }
+        result
+    }
+
+    async fn transact(&self, stream: &mut TcpStream) -> Result<Vec<u16>, SourceError> {
+        let transaction_id = self.transaction_id.fetch_add(1, Ordering::Relaxed);
+        let function = match self.register_type {
+            ModbusRegisterType::Holding => FC_READ_HOLDING_REGISTERS,
+            ModbusRegisterType::Input => FC_READ_INPUT_REGISTERS,
+        };
+        let count = register_count(self.data_type);
+
+        let request = build_request(transaction_id, self.unit_id, function, self.register, count);
+        stream
+            .write_all(&request)
+            .await
+            .map_err(|e| SourceError::ModbusError(e.to_string()))?;
+
+        let mut header = [0u8; MBAP_HEADER_LEN];
+        stream
+            .read_exact(&mut header)
+            .await
+            .map_err(|e| SourceError::ModbusError(e.to_string()))?;
+
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if length < 2 {
+            return Err(SourceError::ModbusError(format!(
+                "Invalid response length {}",
+                length
+            )));
+        }
+        let mut pdu = vec![0u8; length - 1];
+        stream
+            .read_exact(&mut pdu)
+            .await
+            .map_err(|e| SourceError::ModbusError(e.to_string()))?;
+
+        if u16::from_be_bytes([header[0], header[1]]) != transaction_id {
+            return Err(SourceError::ModbusError(
+                "Response transaction id does not match request".to_string(),
+            ));
+        }
+
+        parse_response(function, count, &pdu)
+    }
+}
+
+fn register_count(data_type: ModbusDataType) -> u16 {
+    match data_type {
+        ModbusDataType::U16 | ModbusDataType::I16 => 1,
+        ModbusDataType::U32 | ModbusDataType::I32 | ModbusDataType::F32 => 2,
+    }
+}
+
+fn build_request(
+    transaction_id: u16,
+    unit_id: u8,
+    function: u8,
+    address: u16,
+    count: u16,
+) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(12);
+    frame.extend_from_slice(&transaction_id.to_be_bytes());
+    frame.extend_from_slice(&0u16.to_be_bytes()); // Protocol id
+    frame.extend_from_slice(&6u16.to_be_bytes()); // Remaining length
+    frame.push(unit_id);
+    frame.push(function);
+    frame.extend_from_slice(&address.to_be_bytes());
+    frame.extend_from_slice(&count.to_be_bytes());
+    frame
+}
+
+fn parse_response(function: u8, count: u16, pdu: &[u8]) -> Result<Vec<u16>, SourceError> {
+    match pdu.first() {
+        Some(&code) if code == function | 0x80 => {
+            let exception = pdu.get(1).copied().unwrap_or(0);
+            return Err(SourceError::ModbusError(format!(
+                "Device returned exception {:#04x} ({})",
+                exception,
+                exception_name(exception)
+            )));
+        }
+        Some(&code) if code == function => {}
+        _ => {
+            return Err(SourceError::ModbusError(format!(
+                "Unexpected function code in response: {:?}",
+                pdu.first()
+            )))
+        }
+    }
+
+    let byte_count = pdu.get(1).copied().unwrap_or(0) as usize;
+    let data = &pdu[2.min(pdu.len())..];
+    if byte_count != count as usize * 2 || data.len() < byte_count {
+        return Err(SourceError::ModbusError(format!(
+            "Expected {} registers, got {} bytes",
+            count, byte_count
+        )));
+    }
+
+    Ok(data[..byte_count]
+        .chunks_exact(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]))
+        .collect())
+}
+
+fn exception_name(code: u8) -> &'static str {
+    match code {
+        0x01 => "illegal function",
+        0x02 => "illegal data address",
+        0x03 => "illegal data value",
+        0x04 => "server device failure",
+        0x06 => "server device busy",
+        0x0B => "gateway target device failed to respond",
+        _ => "unknown exception",
+    }
+}
+
+fn decode_registers(
+    registers: &[u16],
+    data_type: ModbusDataType,
+    word_order: ModbusWordOrder,
+) -> Result<f64, SourceError> {
+    let combined = || -> Result<u32, SourceError> {
+        let (high, low) = match (registers, word_order) {
+            ([high, low, ..], ModbusWordOrder::Big) => (*high, *low),
+            ([low, high, ..], ModbusWordOrder::Little) => (*high, *low),
+            _ => {
+                return Err(SourceError::ModbusError(
+                    "Not enough registers for a 32-bit value".to_string(),
+                ))
+            }
+        };
+        Ok(((high as u32) << 16) | low as u32)
+    };
+
+    let first = *registers
+        .first()
+        .ok_or_else(|| SourceError::ModbusError("No registers returned".to_string()))?;
+
+    let value = match data_type {
+        ModbusDataType::U16 => first as f64,
+        ModbusDataType::I16 => first as i16 as f64,
+        ModbusDataType::U32 => combined()? as f64,
+        ModbusDataType::I32 => combined()? as i32 as f64,
+        ModbusDataType::F32 => f32::from_bits(combined()?) as f64,
+    };
+
+    if !value.is_finite() {
+        return Err(SourceError::InvalidValueType(format!("{}", value)));
+    }
+    Ok(value)
+}
+
+#[async_trait]
+impl DataSource for ModbusSource {
+    async fn read_value(&self) -> Result<Reading, SourceError> {
+        let registers = self.read_registers().await?;
+        let raw_value = decode_registers(&registers, self.data_type, self.word_order)?;
+        let value = raw_value * self.multiplier;
+
+        Ok(Reading {
+            value,
+            unit: self.unit.clone(),
+            timestamp: Utc::now(),
+            source_id: self.source_id.clone(),
+        })
+    }
+
+    fn source_id(&self) -> &str {
+        &self.source_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokio::net::TcpListener;
+
+    /// Minimal Modbus TCP server answering register reads from a fixed map.
+    async fn spawn_simulator(registers: HashMap<u16, u16>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let registers = registers.clone();
+                tokio::spawn(async move {
+                    let mut request = [0u8; 12];
+                    while socket.read_exact(&mut request).await.is_ok() {
+                        let function = request[7];
+                        let start = u16::from_be_bytes([request[8], request[9]]);
+                        let count = u16::from_be_bytes([request[10], request[11]]);
+
+                        let values: Option<Vec<u16>> = (start..start + count)
+                            .map(|a| registers.get(&a).copied())
+                            .collect();
+                        let pdu = match values {
+                            Some(values) => {
+                                let mut pdu = vec![function, (count * 2) as u8];
+                                for v in values {
+                                    pdu.extend_from_slice(&v.to_be_bytes());
+                                }
+                                pdu
+                            }
+                            None => vec![function | 0x80, 0x02],
+                        };
+
+                        let mut response = request[..4].to_vec();
+                        response.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+                        response.push(request[6]);
+                        response.extend_from_slice(&pdu);
+                        socket.write_all(&response).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        port
+    }
+
+    fn config(port: u16, address: u16, data_type: ModbusDataType) -> ModbusSourceConfig {
+        ModbusSourceConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            unit_id: 1,
+            address,
+            register_type: ModbusRegisterType::Holding,
+            data_type,
+            word_order: ModbusWordOrder::Big,
+            unit: "kW".to_string(),
+            multiplier: 1.0,
+            timeout_secs: 2,
+        }
+    }
+
+    #[test]
+    fn test_decode_registers() {
+        let bits = 1234.5f32.to_bits();
+        let (high, low) = ((bits >> 16) as u16, bits as u16);
+
+        let big =
+            decode_registers(&[high, low], ModbusDataType::F32, ModbusWordOrder::Big).unwrap();
+        let little =
+            decode_registers(&[low, high], ModbusDataType::F32, ModbusWordOrder::Little).unwrap();
+        assert!((big - 1234.5).abs() < 0.001);
+        assert!((little - 1234.5).abs() < 0.001);
+
+        let negative =
+            decode_registers(&[0xFFFE], ModbusDataType::I16, ModbusWordOrder::Big).unwrap();
+        assert_eq!(negative, -2.0);
+
+        let large =
+            decode_registers(&[0x0001, 0x0000], ModbusDataType::U32, ModbusWordOrder::Big).unwrap();
+        assert_eq!(large, 65536.0);
+    }
+
+    #[tokio::test]
+    async fn test_modbus_source_reads_scaled_register() {
+        let port = spawn_simulator(HashMap::from([(100, 0x0000), (101, 0x3039)])).await;
+
+        let mut config = config(port, 100, ModbusDataType::U32);
+        config.multiplier = 0.1;
+        let source = ModbusSource::new(&config).unwrap();
+
+        let reading = source.read_value().await.unwrap();
+        assert!((reading.value - 1234.5).abs() < 0.001);
+
+        // Second read reuses the open connection
+        let reading = source.read_value().await.unwrap();
+        assert!((reading.value - 1234.5).abs() < 0.001);
+    }
+
+    #[tokio::test]
+    async fn test_modbus_source_reports_exception() {
+        let port = spawn_simulator(HashMap::new()).await;
+        let source = ModbusSource::new(&config(port, 7, ModbusDataType::U16)).unwrap();
+
+        let err = source.read_value().await.unwrap_err();
+        assert!(err.to_string().contains("illegal data address"));
+    }
+}
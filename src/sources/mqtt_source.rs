@@ -0,0 +1,317 @@
+use super::{extract_json_value, DataSource, Reading};
+use crate::config::MqttSourceConfig;
+use crate::error::SourceError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Pause before reconnecting after the broker connection drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy)]
+struct LatestValue {
+    value: f64,
+    received_at: Instant,
+    timestamp: DateTime<Utc>,
+}
+
+/// Serves the most recent value published on an MQTT topic.
+///
+/// A background task keeps the broker subscription alive and caches the last
+/// message; `read_value` never waits for the network.
+pub struct MqttSource {
+    topic: String,
+    unit: String,
+    multiplier: f64,
+    max_age: Duration,
+    latest: Arc<Mutex<Option<LatestValue>>>,
+    task: JoinHandle<()>,
+    source_id: String,
+}
+
+impl MqttSource {
+    pub fn new(config: &MqttSourceConfig, polling_interval: Duration) -> Result<Self, SourceError> {
+        let qos = match config.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            other => {
+                return Err(SourceError::ParseError(format!(
+                    "Invalid MQTT QoS: {}",
+                    other
+                )))
+            }
+        };
+
+        let client_id = config
+            .client_id
+            .clone()
+            .unwrap_or_else(|| format!("agentquelia-{}", uuid::Uuid::new_v4().simple()));
+
+        let mut options = MqttOptions::new(client_id, &config.host, config.port);
+        options.set_keep_alive(Duration::from_secs(30));
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.clone().unwrap_or_default());
+        }
+
+        let (client, event_loop) = AsyncClient::new(options, 10);
+        let latest = Arc::new(Mutex::new(None));
+        let source_id = format!("mqtt:{}:{}/{}", config.host, config.port, config.topic);
+
+        let task = tokio::spawn(run_event_loop(
+            client,
+            event_loop,
+            config.topic.clone(),
+            qos,
+            config.json_path.clone(),
+            latest.clone(),
+            source_id.clone(),
+        ));
+
+        Ok(Self {
+            topic: config.topic.clone(),
+            unit: config.unit.clone(),
+            multiplier: config.multiplier,
+            max_age: polling_interval * config.stale_after_intervals,
+            latest,
+            task,
+            source_id,
+        })
+    }
+}
+
+impl Drop for MqttSource {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run_event_loop(
+    client: AsyncClient,
+    mut event_loop: EventLoop,
+    topic: String,
+    qos: QoS,
+    json_path: Option<String>,
+    latest: Arc<Mutex<Option<LatestValue>>>,
+    source_id: String,
+) {
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                // Subscriptions don't survive a clean-session reconnect, so renew them every time
+                info!(source = %source_id, topic = %topic, "Connected to MQTT broker");
+                if let Err(e) = client.try_subscribe(&topic, qos) {
+                    warn!(source = %source_id, error = %e, "Failed to subscribe to MQTT topic");
+                }
+            }
+            Ok(Event::Incoming(Packet::Publish(publish))) => {
+                match parse_payload(&publish.payload, json_path.as_deref()) {
+                    Ok(value) => {
+                        debug!(source = %source_id, value = value, "Received MQTT message");
+                        *latest.lock().unwrap() = Some(LatestValue {
+                            value,
+                            received_at: Instant::now(),
+                            timestamp: Utc::now(),
+                        });
+                    }
+                    Err(e) => {
+                        warn!(
+                            source = %source_id,
+                            topic = %publish.topic,
+                            error = %e,
+                            "Ignoring unparseable MQTT message"
+                        );
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                warn!(source = %source_id, error = %e, "MQTT connection error, reconnecting");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+fn parse_payload(payload: &[u8], json_path: Option<&str>) -> Result<f64, SourceError> {
+    let text = std::str::from_utf8(payload)
+        .map_err(|_| SourceError::ParseError("Payload is not valid UTF-8".to_string()))?;
+
+    match json_path {
+        Some(path) => {
+            let json: serde_json::Value =
+                serde_json::from_str(text).map_err(|e| SourceError::JsonError(e.to_string()))?;
+            extract_json_value(&json, path)
+        }
+        None => text
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| SourceError::InvalidValueType(format!("'{}'", text.trim()))),
+    }
+}
+
+#[async_trait]
+impl DataSource for MqttSource {
+    async fn read_value(&self) -> Result<Reading, SourceError> {
+        let latest = *self.latest.lock().unwrap();
+
+        let latest = latest.ok_or_else(|| {
+            SourceError::Stale(format!("No message received yet on topic '{}'", self.topic))
+        })?;
+
+        if latest.received_at.elapsed() > self.max_age {
+            return Err(SourceError::Stale(format!(
+                "No message on topic '{}' for {} seconds",
+                self.topic,
+                latest.received_at.elapsed().as_secs()
+            )));
+        }
+
+        Ok(Reading {
+            value: latest.value * self.multiplier,
+            unit: self.unit.clone(),
+            timestamp: latest.timestamp,
+            source_id: self.source_id.clone(),
+        })
+    }
+
+    fn source_id(&self) -> &str {
+        &self.source_id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn read_packet(socket: &mut TcpStream) -> (u8, Vec<u8>) {
+        let packet_type = socket.read_u8().await.unwrap();
+        let mut length = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = socket.read_u8().await.unwrap();
+            length |= ((byte & 0x7F) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        let mut body = vec![0u8; length];
+        socket.read_exact(&mut body).await.unwrap();
+        (packet_type, body)
+    }
+
+    /// Accepts one client, acknowledges its subscription and publishes `payload` to it.
+    async fn spawn_broker(payload: &'static [u8]) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+
+            let (packet_type, _) = read_packet(&mut socket).await;
+            assert_eq!(packet_type, 0x10, "expected CONNECT");
+            socket.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+
+            let (packet_type, body) = read_packet(&mut socket).await;
+            assert_eq!(packet_type, 0x82, "expected SUBSCRIBE");
+            let topic_len = u16::from_be_bytes([body[2], body[3]]) as usize;
+            let topic = body[4..4 + topic_len].to_vec();
+            socket
+                .write_all(&[0x90, 0x03, body[0], body[1], 0x00])
+                .await
+                .unwrap();
+
+            let mut publish = vec![0x30, (2 + topic.len() + payload.len()) as u8];
+            publish.extend_from_slice(&(topic.len() as u16).to_be_bytes());
+            publish.extend_from_slice(&topic);
+            publish.extend_from_slice(payload);
+            socket.write_all(&publish).await.unwrap();
+
+            // Keep the connection open
+            while read_packet(&mut socket).await.0 != 0xE0 {}
+        });
+
+        port
+    }
+
+    fn config() -> MqttSourceConfig {
+        MqttSourceConfig {
+            // Nothing listens here, the event loop just keeps retrying
+            host: "127.0.0.1".to_string(),
+            port: 1,
+            topic: "site/meter/power".to_string(),
+            json_path: Some("$.power".to_string()),
+            unit: "kW".to_string(),
+            multiplier: 0.001,
+            client_id: None,
+            username: None,
+            password: None,
+            qos: 0,
+            stale_after_intervals: 3,
+        }
+    }
+
+    #[test]
+    fn test_parse_payload() {
+        let value = parse_payload(br#"{"power": 1500}"#, Some("$.power")).unwrap();
+        assert_eq!(value, 1500.0);
+
+        let value = parse_payload(b" 42.5\n", None).unwrap();
+        assert_eq!(value, 42.5);
+
+        assert!(parse_payload(b"not a number", None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mqtt_source_receives_published_value() {
+        let mut config = config();
+        config.port = spawn_broker(br#"{"power": 2500}"#).await;
+        let source = MqttSource::new(&config, Duration::from_secs(10)).unwrap();
+
+        let mut reading = source.read_value().await;
+        for _ in 0..50 {
+            if reading.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            reading = source.read_value().await;
+        }
+
+        assert!((reading.unwrap().value - 2.5).abs() < 0.0001);
+    }
+
+    #[tokio::test]
+    async fn test_mqtt_source_serves_cached_value_until_stale() {
+        let source = MqttSource::new(&config(), Duration::from_secs(10)).unwrap();
+        assert!(matches!(
+            source.read_value().await,
+            Err(SourceError::Stale(_))
+        ));
+
+        *source.latest.lock().unwrap() = Some(LatestValue {
+            value: 1500.0,
+            received_at: Instant::now(),
+            timestamp: Utc::now(),
+        });
+        let reading = source.read_value().await.unwrap();
+        assert!((reading.value - 1.5).abs() < 0.0001);
+
+        // Three 10 s intervals without a message
+        *source.latest.lock().unwrap() = Some(LatestValue {
+            value: 1500.0,
+            received_at: Instant::now() - Duration::from_secs(31),
+            timestamp: Utc::now(),
+        });
+        assert!(matches!(
+            source.read_value().await,
+            Err(SourceError::Stale(_))
+        ));
+    }
+}
@@ -0,0 +1,50 @@
+use crate::config::AgentConfig;
+use crate::error::SourceError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Where a source keeps state of the given `kind` between runs when no path is configured.
+pub fn default_state_path(source_name: &str, kind: &str) -> PathBuf {
+    let file_name: String = source_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    AgentConfig::default_data_dir()
+        .map(|p| p.join("state"))
+        .unwrap_or_else(|| PathBuf::from("state"))
+        .join(format!("{}.{}.json", file_name, kind))
+}
+
+/// Load a state file. A missing or unreadable file yields `None`, so the source starts fresh.
+pub fn load_state<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let content = std::fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(state) => Some(state),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Ignoring corrupt state file");
+            None
+        }
+    }
+}
+
+/// Replace a state file atomically so a crash never leaves it half written.
+pub fn save_state<T: Serialize>(path: &Path, state: &T) -> Result<(), SourceError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| SourceError::StateError(e.to_string()))?;
+    }
+
+    let content =
+        serde_json::to_string(state).map_err(|e| SourceError::StateError(e.to_string()))?;
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, content).map_err(|e| SourceError::StateError(e.to_string()))?;
+    std::fs::rename(&tmp_path, path).map_err(|e| SourceError::StateError(e.to_string()))
+}
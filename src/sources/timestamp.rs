@@ -0,0 +1,254 @@
+use crate::error::SourceError;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, LocalResult, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Naive layouts accepted by the `iso` format after RFC 3339
+const ISO_NAIVE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+];
+
+enum TimestampFormat {
+    /// RFC 3339, or ISO 8601 without an offset (interpreted in the source timezone)
+    Iso,
+    /// Unix time in seconds, fractions allowed
+    Epoch,
+    /// Unix time in milliseconds
+    EpochMillis,
+    /// A strftime pattern such as `%d/%m/%Y %H:%M`
+    Pattern(String),
+}
+
+enum SourceTimezone {
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+/// Turns the measurement time written by a source into a UTC timestamp.
+pub struct TimestampParser {
+    format: TimestampFormat,
+    timezone: SourceTimezone,
+}
+
+impl TimestampParser {
+    /// `format` is `iso` (default), `epoch`, `epoch_ms` or a strftime pattern.
+    /// `timezone` applies to timestamps written without an offset: an IANA name
+    /// like `Europe/Paris` or a fixed offset like `+01:00`. Defaults to UTC.
+    pub fn new(format: Option<&str>, timezone: Option<&str>) -> Result<Self, SourceError> {
+        let format = match format.map(str::trim) {
+            None | Some("iso") | Some("rfc3339") => TimestampFormat::Iso,
+            Some("epoch") => TimestampFormat::Epoch,
+            Some("epoch_ms") => TimestampFormat::EpochMillis,
+            Some(pattern) => {
+                if !pattern.contains('%')
+                    || StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error))
+                {
+                    return Err(SourceError::ParseError(format!(
+                        "Invalid timestamp format: {}",
+                        pattern
+                    )));
+                }
+                TimestampFormat::Pattern(pattern.to_string())
+            }
+        };
+
+        let timezone = match timezone.map(str::trim) {
+            None | Some("") => SourceTimezone::Fixed(utc_offset()),
+            Some(name) => {
+                if let Ok(tz) = name.parse::<Tz>() {
+                    SourceTimezone::Named(tz)
+                } else if let Ok(offset) = name.parse::<FixedOffset>() {
+                    SourceTimezone::Fixed(offset)
+                } else {
+                    return Err(SourceError::ParseError(format!(
+                        "Unknown timezone: {}",
+                        name
+                    )));
+                }
+            }
+        };
+
+        Ok(Self { format, timezone })
+    }
+
+    pub fn parse(&self, text: &str) -> Result<DateTime<Utc>, SourceError> {
+        let text = text.trim();
+        match &self.format {
+            TimestampFormat::Iso => {
+                if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+                    return Ok(dt.with_timezone(&Utc));
+                }
+                if let Ok(dt) = DateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f%z") {
+                    return Ok(dt.with_timezone(&Utc));
+                }
+                for format in ISO_NAIVE_FORMATS {
+                    if let Ok(naive) = NaiveDateTime::parse_from_str(text, format) {
+                        return self.localize(naive, text);
+                    }
+                }
+                if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+                    return self.localize(date.and_hms_opt(0, 0, 0).unwrap(), text);
+                }
+                Err(SourceError::InvalidTimestamp(format!(
+                    "'{}' is not an ISO 8601 timestamp",
+                    text
+                )))
+            }
+            TimestampFormat::Epoch | TimestampFormat::EpochMillis => {
+                let number = text.parse::<f64>().map_err(|_| {
+                    SourceError::InvalidTimestamp(format!("'{}' is not a Unix time", text))
+                })?;
+                self.epoch_to_utc(number)
+            }
+            TimestampFormat::Pattern(pattern) => {
+                if let Ok(dt) = DateTime::parse_from_str(text, pattern) {
+                    return Ok(dt.with_timezone(&Utc));
+                }
+                if let Ok(naive) = NaiveDateTime::parse_from_str(text, pattern) {
+                    return self.localize(naive, text);
+                }
+                if let Ok(date) = NaiveDate::parse_from_str(text, pattern) {
+                    return self.localize(date.and_hms_opt(0, 0, 0).unwrap(), text);
+                }
+                Err(SourceError::InvalidTimestamp(format!(
+                    "'{}' does not match format '{}'",
+                    text, pattern
+                )))
+            }
+        }
+    }
+
+    /// Parse a timestamp found in a JSON document. Numbers are only accepted as Unix time.
+    pub fn parse_json(&self, value: &serde_json::Value) -> Result<DateTime<Utc>, SourceError> {
+        match (value, &self.format) {
+            (serde_json::Value::String(s), _) => self.parse(s),
+            (
+                serde_json::Value::Number(n),
+                TimestampFormat::Epoch | TimestampFormat::EpochMillis,
+            ) => self.epoch_to_utc(n.as_f64().unwrap_or(f64::NAN)),
+            (other, _) => Err(SourceError::InvalidTimestamp(format!(
+                "Expected a timestamp, got {}",
+                other
+            ))),
+        }
+    }
+
+    fn epoch_to_utc(&self, number: f64) -> Result<DateTime<Utc>, SourceError> {
+        let millis = match self.format {
+            TimestampFormat::EpochMillis => number,
+            _ => number * 1000.0,
+        };
+        if !millis.is_finite() {
+            return Err(SourceError::InvalidTimestamp(format!(
+                "'{}' is not a Unix time",
+                number
+            )));
+        }
+
+        Utc.timestamp_millis_opt(millis.round() as i64)
+            .single()
+            .ok_or_else(|| SourceError::InvalidTimestamp(format!("'{}' is out of range", number)))
+    }
+
+    /// Read a timestamp without an offset as local time in the source timezone.
+    fn localize(&self, naive: NaiveDateTime, text: &str) -> Result<DateTime<Utc>, SourceError> {
+        let local = match &self.timezone {
+            SourceTimezone::Fixed(offset) => offset
+                .from_local_datetime(&naive)
+                .map(|dt| dt.with_timezone(&Utc)),
+            SourceTimezone::Named(tz) => tz
+                .from_local_datetime(&naive)
+                .map(|dt| dt.with_timezone(&Utc)),
+        };
+
+        match local {
+            LocalResult::Single(dt) => Ok(dt),
+            // The hour repeated when clocks go back: take its first occurrence
+            LocalResult::Ambiguous(earliest, _) => Ok(earliest),
+            LocalResult::None => Err(SourceError::InvalidTimestamp(format!(
+                "'{}' does not exist in the source timezone (daylight saving gap)",
+                text
+            ))),
+        }
+    }
+}
+
+fn utc_offset() -> FixedOffset {
+    FixedOffset::east_opt(0).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse_formats() {
+        let iso = TimestampParser::new(None, None).unwrap();
+        assert_eq!(
+            iso.parse("2024-03-01T10:15:00+01:00").unwrap(),
+            utc("2024-03-01T09:15:00Z")
+        );
+        assert_eq!(
+            iso.parse("2024-03-01 10:15:30").unwrap(),
+            utc("2024-03-01T10:15:30Z")
+        );
+        assert!(iso.parse("yesterday").is_err());
+
+        let epoch = TimestampParser::new(Some("epoch"), None).unwrap();
+        assert_eq!(
+            epoch.parse("1709288100").unwrap(),
+            utc("2024-03-01T10:15:00Z")
+        );
+        assert_eq!(
+            epoch.parse_json(&serde_json::json!(1709288100.5)).unwrap(),
+            utc("2024-03-01T10:15:00.5Z")
+        );
+
+        let millis = TimestampParser::new(Some("epoch_ms"), None).unwrap();
+        assert_eq!(
+            millis
+                .parse_json(&serde_json::json!(1709288100000u64))
+                .unwrap(),
+            utc("2024-03-01T10:15:00Z")
+        );
+        assert!(iso.parse_json(&serde_json::json!(1709288100)).is_err());
+
+        let pattern = TimestampParser::new(Some("%d/%m/%Y %H:%M"), Some("+02:00")).unwrap();
+        assert_eq!(
+            pattern.parse("01/03/2024 10:15").unwrap(),
+            utc("2024-03-01T08:15:00Z")
+        );
+
+        assert!(TimestampParser::new(Some("dd/mm/yyyy"), None).is_err());
+        assert!(TimestampParser::new(None, Some("Mars/Olympus")).is_err());
+    }
+
+    #[test]
+    fn test_named_timezone_follows_daylight_saving() {
+        let parser = TimestampParser::new(None, Some("Europe/Paris")).unwrap();
+        // CET in winter, CEST in summer
+        assert_eq!(
+            parser.parse("2024-01-15 12:00:00").unwrap(),
+            utc("2024-01-15T11:00:00Z")
+        );
+        assert_eq!(
+            parser.parse("2024-07-15 12:00:00").unwrap(),
+            utc("2024-07-15T10:00:00Z")
+        );
+        // 02:30 is skipped when clocks go forward, and happens twice when they go back
+        assert!(parser.parse("2024-03-31 02:30:00").is_err());
+        assert_eq!(
+            parser.parse("2024-10-27 02:30:00").unwrap(),
+            utc("2024-10-27T00:30:00Z")
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use crate::error::SourceError;
+use roxmltree::{Document, Node};
+
+/// A small subset of XPath for picking one value out of an XML document.
+///
+/// Supports child (`/a/b`) and descendant (`//b`) steps, `*`, a 1-based position
+/// (`b[2]`) or attribute test (`b[@name='P']`) per step, and a final `@attr` or
+/// `text()`. Element names match regardless of namespace.
+#[derive(Debug)]
+pub struct XmlPath {
+    text: String,
+    steps: Vec<Step>,
+    attribute: Option<String>,
+}
+
+#[derive(Debug)]
+struct Step {
+    descendant: bool,
+    name: String,
+    filter: Option<Filter>,
+}
+
+#[derive(Debug)]
+enum Filter {
+    Position(usize),
+    Attribute(String, String),
+}
+
+impl XmlPath {
+    pub fn parse(text: &str) -> Result<Self, SourceError> {
+        let invalid = |reason: &str| {
+            SourceError::ParseError(format!("Invalid XML path '{}': {}", text, reason))
+        };
+
+        let mut steps = Vec::new();
+        let mut attribute = None;
+        let mut rest = text.trim();
+        if !rest.starts_with('/') {
+            return Err(invalid("must start with / or //"));
+        }
+
+        while !rest.is_empty() {
+            let descendant = rest.starts_with("//");
+            rest = rest
+                .strip_prefix("//")
+                .or_else(|| rest.strip_prefix('/'))
+                .ok_or_else(|| invalid("expected /"))?;
+
+            let end = step_end(rest);
+            let step = &rest[..end];
+            rest = &rest[end..];
+
+            if let Some(name) = step.strip_prefix('@') {
+                if !rest.is_empty() || name.is_empty() {
+                    return Err(invalid("an attribute must be the last step"));
+                }
+                attribute = Some(name.to_string());
+            } else if step == "text()" {
+                if !rest.is_empty() {
+                    return Err(invalid("text() must be the last step"));
+                }
+            } else {
+                steps.push(parse_step(step, descendant).map_err(|reason| invalid(&reason))?);
+            }
+        }
+
+        if steps.is_empty() {
+            return Err(invalid("no element to select"));
+        }
+
+        Ok(Self {
+            text: text.to_string(),
+            steps,
+            attribute,
+        })
+    }
+
+    /// Text of the first matching node, or the attribute's value.
+    pub fn select(&self, document: &Document) -> Result<String, SourceError> {
+        let mut nodes = vec![document.root()];
+        for step in &self.steps {
+            let mut next: Vec<Node> = Vec::new();
+            for node in &nodes {
+                for matched in step.apply(*node) {
+                    if !next.contains(&matched) {
+                        next.push(matched);
+                    }
+                }
+            }
+            nodes = next;
+        }
+
+        let not_found =
+            || SourceError::ValueNotFound(format!("No value found at XML path: {}", self.text));
+        let node = nodes.first().ok_or_else(not_found)?;
+        match &self.attribute {
+            Some(name) => node
+                .attributes()
+                .find(|a| a.name() == name)
+                .map(|a| a.value().to_string())
+                .ok_or_else(not_found),
+            None => Ok(node
+                .descendants()
+                .filter(|n| n.is_text())
+                .filter_map(|n| n.text())
+                .collect()),
+        }
+    }
+}
+
+impl Step {
+    fn apply<'a, 'input>(&self, node: Node<'a, 'input>) -> Vec<Node<'a, 'input>> {
+        let candidates: Vec<Node> = if self.descendant {
+            node.descendants()
+                .filter(|n| *n != node && self.matches(n))
+                .collect()
+        } else {
+            node.children().filter(|n| self.matches(n)).collect()
+        };
+
+        match &self.filter {
+            None => candidates,
+            Some(Filter::Position(position)) => {
+                candidates.get(position - 1).copied().into_iter().collect()
+            }
+            Some(Filter::Attribute(name, value)) => candidates
+                .into_iter()
+                .filter(|n| {
+                    n.attributes()
+                        .any(|a| a.name() == name && a.value() == value)
+                })
+                .collect(),
+        }
+    }
+
+    fn matches(&self, node: &Node) -> bool {
+        node.is_element() && (self.name == "*" || node.tag_name().name() == self.name)
+    }
+}
+
+/// Where the current step ends: the next `/` outside a predicate.
+fn step_end(rest: &str) -> usize {
+    let mut depth = 0;
+    let mut quote = None;
+    for (i, c) in rest.char_indices() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('[', None) => depth += 1,
+            (']', None) => depth -= 1,
+            ('/', None) if depth == 0 => return i,
+            _ => {}
+        }
+    }
+    rest.len()
+}
+
+fn parse_step(step: &str, descendant: bool) -> Result<Step, String> {
+    let (name, filter) = match step.find('[') {
+        Some(open) => {
+            let predicate = step[open + 1..]
+                .strip_suffix(']')
+                .ok_or_else(|| format!("unclosed predicate in '{}'", step))?;
+            (&step[..open], Some(parse_predicate(predicate)?))
+        }
+        None => (step, None),
+    };
+
+    let valid_name = name == "*"
+        || (!name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || "_-.:".contains(c)));
+    if !valid_name {
+        return Err(format!("invalid element name '{}'", name));
+    }
+
+    // Prefixes are accepted but ignored, like the namespaces they stand for
+    let name = name.rsplit(':').next().unwrap_or(name);
+    Ok(Step {
+        descendant,
+        name: name.to_string(),
+        filter,
+    })
+}
+
+fn parse_predicate(predicate: &str) -> Result<Filter, String> {
+    let predicate = predicate.trim();
+    if let Ok(position) = predicate.parse::<usize>() {
+        return match position {
+            0 => Err("positions start at 1".to_string()),
+            _ => Ok(Filter::Position(position)),
+        };
+    }
+
+    let (name, value) = predicate
+        .strip_prefix('@')
+        .and_then(|p| p.split_once('='))
+        .ok_or_else(|| format!("unsupported predicate [{}]", predicate))?;
+    let value = value.trim();
+    let unquoted = value
+        .strip_prefix('\'')
+        .and_then(|v| v.strip_suffix('\''))
+        .or_else(|| value.strip_prefix('"').and_then(|v| v.strip_suffix('"')))
+        .ok_or_else(|| format!("the value in [{}] must be quoted", predicate))?;
+    Ok(Filter::Attribute(
+        name.trim().to_string(),
+        unquoted.to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<?xml version="1.0"?>
+        <Device xmlns="urn:example">
+          <Measurements>
+            <Value name="Pac" unit="W"> 1234.5 </Value>
+            <Value name="Uac" unit="V">230.1</Value>
+          </Measurements>
+          <Counter total="98765"/>
+        </Device>"#;
+
+    fn select(path: &str) -> Result<String, SourceError> {
+        let document = Document::parse(XML).unwrap();
+        XmlPath::parse(path).unwrap().select(&document)
+    }
+
+    #[test]
+    fn test_select_values() {
+        assert_eq!(
+            select("/Device/Measurements/Value").unwrap().trim(),
+            "1234.5"
+        );
+        assert_eq!(select("//Value[2]").unwrap(), "230.1");
+        assert_eq!(select("//Value[@name='Uac']/text()").unwrap(), "230.1");
+        assert_eq!(select("//Value[@name=\"Pac\"]/@unit").unwrap(), "W");
+        assert_eq!(select("/Device/*/Value[1]").unwrap().trim(), "1234.5");
+        assert_eq!(select("//Counter/@total").unwrap(), "98765");
+        assert!(matches!(
+            select("//Value[@name='Qac']"),
+            Err(SourceError::ValueNotFound(_))
+        ));
+
+        for invalid in [
+            "Device",
+            "//Value[0]",
+            "//Value[name]",
+            "//@unit/Value",
+            "/",
+        ] {
+            assert!(
+                XmlPath::parse(invalid).is_err(),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
+}
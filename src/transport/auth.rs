@@ -0,0 +1,257 @@
+use super::{error_for_status, map_request_error, retry_after_header};
+use crate::config::HttpAuthConfig;
+use crate::error::TransportError;
+use reqwest::RequestBuilder;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+/// Tokens are renewed this long before the server says they expire
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+/// Adds the configured credentials to outgoing requests.
+///
+/// OAuth2 client-credentials tokens are fetched on first use and reused until
+/// shortly before they expire, or until the server rejects them.
+pub struct HttpAuth {
+    config: HttpAuthConfig,
+    token: Mutex<Option<CachedToken>>,
+}
+
+struct CachedToken {
+    access_token: String,
+    expires_at: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+impl HttpAuth {
+    pub fn new(config: &HttpAuthConfig) -> Self {
+        Self {
+            config: config.clone(),
+            token: Mutex::new(None),
+        }
+    }
+
+    pub async fn apply(
+        &self,
+        client: &reqwest::Client,
+        request: RequestBuilder,
+    ) -> Result<RequestBuilder, TransportError> {
+        match &self.config {
+            HttpAuthConfig::Basic { username, password } => {
+                Ok(request.basic_auth(username, Some(password)))
+            }
+            HttpAuthConfig::Bearer { token } => Ok(request.bearer_auth(token)),
+            HttpAuthConfig::OAuth2 { .. } => {
+                let mut cached = self.token.lock().await;
+                let fresh = cached.as_ref().is_some_and(|t| {
+                    t.expires_at
+                        .map_or(true, |at| Instant::now() + TOKEN_REFRESH_MARGIN < at)
+                });
+                if !fresh {
+                    *cached = Some(self.fetch_token(client).await?);
+                }
+                let token = &cached.as_ref().unwrap().access_token;
+                Ok(request.bearer_auth(token))
+            }
+        }
+    }
+
+    /// Forget a cached token the server refused, so the next request gets a new one.
+    pub async fn invalidate(&self) {
+        self.token.lock().await.take();
+    }
+
+    async fn fetch_token(&self, client: &reqwest::Client) -> Result<CachedToken, TransportError> {
+        let HttpAuthConfig::OAuth2 {
+            token_url,
+            client_id,
+            client_secret,
+            scope,
+        } = &self.config
+        else {
+            unreachable!("only OAuth2 fetches tokens")
+        };
+
+        let mut form = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", client_id.as_str()),
+            ("client_secret", client_secret.as_str()),
+        ];
+        if let Some(scope) = scope {
+            form.push(("scope", scope.as_str()));
+        }
+
+        let response = client
+            .post(token_url)
+            .form(&form)
+            .send()
+            .await
+            .map_err(map_request_error)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = retry_after_header(&response);
+            let body = response.text().await.unwrap_or_default();
+            let message = format!("Token request failed: {}", body);
+            // An unavailable or rate-limited token endpoint is retried like any other server
+            return Err(match error_for_status(status, retry_after, message) {
+                TransportError::InvalidResponse(message) => TransportError::AuthFailed(message),
+                e => e,
+            });
+        }
+
+        let token: TokenResponse = response.json().await.map_err(|e| {
+            TransportError::InvalidResponse(format!("Invalid token response: {}", e))
+        })?;
+        debug!(
+            token_url = %token_url,
+            expires_in = token.expires_in,
+            "Obtained OAuth2 access token"
+        );
+
+        Ok(CachedToken {
+            access_token: token.access_token,
+            expires_at: token
+                .expires_in
+                .map(|secs| Instant::now() + Duration::from_secs(secs)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::HttpSinkConfig;
+    use crate::error::Retriable;
+    use crate::outbox::OutboxEntry;
+    use crate::sources::Reading;
+    use crate::test_server::{response, MockServer};
+    use crate::transport::{HttpSink, Transport};
+    use std::collections::{HashMap, HashSet};
+    use std::sync::{Arc, Mutex as StdMutex};
+
+    /// Token endpoint and API behind one address.
+    #[derive(Default)]
+    struct Server {
+        /// `expires_in` of each token handed out, in order; 0 answers 503 instead
+        lifetimes: Vec<u64>,
+        token_requests: usize,
+        /// Tokens the API no longer accepts
+        revoked: HashSet<String>,
+        /// The bearer token of each API request
+        api_requests: Vec<String>,
+    }
+
+    async fn spawn_server(lifetimes: Vec<u64>) -> (String, Arc<StdMutex<Server>>) {
+        let server = Arc::new(StdMutex::new(Server {
+            lifetimes,
+            ..Default::default()
+        }));
+
+        let state = server.clone();
+        let mock = MockServer::start(move |request| {
+            let mut server = state.lock().unwrap();
+            if request.method == "POST" && request.path == "/token" {
+                let issued = server.token_requests;
+                server.token_requests += 1;
+                match server.lifetimes[issued] {
+                    0 => response("503 Service Unavailable", &[], ""),
+                    expires_in => response(
+                        "200 OK",
+                        &[("Content-Type", "application/json")],
+                        &format!(
+                            r#"{{"access_token": "token-{}", "expires_in": {}}}"#,
+                            issued, expires_in
+                        ),
+                    ),
+                }
+            } else {
+                let token = request.header("authorization").unwrap_or_default();
+                let token = token.trim_start_matches("Bearer ").to_string();
+                let refused = server.revoked.contains(&token);
+                server.api_requests.push(token);
+                match refused {
+                    true => response("401 Unauthorized", &[], ""),
+                    false => response("200 OK", &[("Content-Type", "application/json")], "{}"),
+                }
+            }
+        })
+        .await;
+        (mock.url, server)
+    }
+
+    fn entry() -> OutboxEntry {
+        OutboxEntry {
+            api_key: "poi-key".to_string(),
+            reading: Reading {
+                value: 1.0,
+                unit: "kW".to_string(),
+                timestamp: chrono::Utc::now(),
+                source_id: "test".to_string(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oauth2_token_is_cached_and_renewed() {
+        // Unavailable, then a long-lived token, one about to expire, and another long-lived one
+        let (url, server) = spawn_server(vec![0, 3600, 30, 3600]).await;
+        let sink = HttpSink::new(&HttpSinkConfig {
+            url: format!("{}/ingest", url),
+            method: "POST".to_string(),
+            body_template: r#"{"power": {value}}"#.to_string(),
+            headers: HashMap::new(),
+            auth: Some(HttpAuthConfig::OAuth2 {
+                token_url: format!("{}/token", url),
+                client_id: "agent".to_string(),
+                client_secret: "secret".to_string(),
+                scope: Some("write".to_string()),
+            }),
+            timeout_secs: 5,
+        })
+        .unwrap();
+        let entry = entry();
+        let call = || sink.send(&entry);
+        let state = || {
+            let server = server.lock().unwrap();
+            (server.token_requests, server.api_requests.clone())
+        };
+
+        // A token endpoint that is down is worth retrying
+        let err = call().await.unwrap_err();
+        assert!(matches!(
+            err,
+            TransportError::ServerError { status: 503, .. }
+        ));
+        assert!(err.is_retriable());
+
+        // Fetched once, then reused
+        call().await.unwrap();
+        call().await.unwrap();
+        assert_eq!(state(), (2, vec!["token-1".to_string(); 2]));
+
+        // Refused by the API: the next call fetches a new one
+        server.lock().unwrap().revoked.insert("token-1".to_string());
+        assert!(call().await.is_err());
+        call().await.unwrap();
+        assert_eq!(state().0, 3);
+        assert_eq!(state().1.last().unwrap(), "token-2");
+
+        // token-2 expires within the refresh margin, so it isn't reused
+        call().await.unwrap();
+        call().await.unwrap();
+        let (token_requests, api_requests) = state();
+        assert_eq!(token_requests, 4);
+        assert_eq!(
+            api_requests[api_requests.len() - 2..],
+            ["token-3", "token-3"]
+        );
+    }
+}
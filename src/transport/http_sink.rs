@@ -0,0 +1,174 @@
+use super::{error_for_status, map_request_error, retry_after_header, HttpAuth, Transport};
+use crate::config::HttpSinkConfig;
+use crate::error::TransportError;
+use crate::outbox::OutboxEntry;
+use crate::sources::Reading;
+use async_trait::async_trait;
+use chrono::{SecondsFormat, Utc};
+use regex_lite::{Captures, Regex};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::Method;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Posts each reading to an arbitrary HTTP endpoint as a JSON body built from a template.
+pub struct HttpSink {
+    client: reqwest::Client,
+    url: String,
+    method: Method,
+    headers: HeaderMap,
+    auth: Option<HttpAuth>,
+    body_template: String,
+    placeholder: Regex,
+}
+
+impl HttpSink {
+    pub fn new(config: &HttpSinkConfig) -> Result<Self, TransportError> {
+        let method = Method::from_str(&config.method.to_uppercase()).map_err(|_| {
+            TransportError::InvalidConfig(format!("Invalid HTTP method: {}", config.method))
+        })?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        for (key, value) in &config.headers {
+            let header_name = HeaderName::from_str(key).map_err(|_| {
+                TransportError::InvalidConfig(format!("Invalid header name: {}", key))
+            })?;
+            let header_value = HeaderValue::from_str(value).map_err(|_| {
+                TransportError::InvalidConfig(format!("Invalid header value: {}", value))
+            })?;
+            headers.insert(header_name, header_value);
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .map_err(|e| TransportError::Network(e.to_string()))?;
+
+        let sink = Self {
+            client,
+            url: config.url.clone(),
+            method,
+            headers,
+            auth: config.auth.as_ref().map(HttpAuth::new),
+            body_template: config.body_template.clone(),
+            placeholder: Regex::new(r"\{(value|unit|timestamp|api_key|source_id)\}").unwrap(),
+        };
+
+        // Catch template mistakes at startup rather than on every send
+        let sample = OutboxEntry {
+            api_key: "key".to_string(),
+            reading: Reading {
+                value: 1.0,
+                unit: "kW".to_string(),
+                timestamp: Utc::now(),
+                source_id: "source".to_string(),
+            },
+        };
+        serde_json::from_str::<serde_json::Value>(&sink.render(&sample)).map_err(|e| {
+            TransportError::InvalidConfig(format!("Body template is not valid JSON: {}", e))
+        })?;
+
+        Ok(sink)
+    }
+
+    /// Fill the body template. Strings are JSON-escaped but not quoted, so the
+    /// template decides whether a placeholder sits inside a string.
+    fn render(&self, entry: &OutboxEntry) -> String {
+        let reading = &entry.reading;
+        self.placeholder
+            .replace_all(&self.body_template, |caps: &Captures| match &caps[1] {
+                "value" => serde_json::Value::from(reading.value).to_string(),
+                "unit" => escape(&reading.unit),
+                "timestamp" => reading.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+                "api_key" => escape(&entry.api_key),
+                "source_id" => escape(&reading.source_id),
+                _ => caps[0].to_string(),
+            })
+            .into_owned()
+    }
+}
+
+fn escape(text: &str) -> String {
+    let quoted = serde_json::Value::from(text).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+#[async_trait]
+impl Transport for HttpSink {
+    async fn send(&self, entry: &OutboxEntry) -> Result<(), TransportError> {
+        let mut request = self
+            .client
+            .request(self.method.clone(), &self.url)
+            .headers(self.headers.clone())
+            .body(self.render(entry));
+
+        if let Some(auth) = &self.auth {
+            request = auth.apply(&self.client, request).await?;
+        }
+
+        let response = request.send().await.map_err(map_request_error)?;
+        let status = response.status();
+
+        if status.is_success() {
+            return Ok(());
+        }
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            if let Some(auth) = &self.auth {
+                auth.invalidate().await;
+            }
+        }
+
+        let retry_after = retry_after_header(&response);
+        let error_body = response.text().await.unwrap_or_default();
+        Err(error_for_status(status, retry_after, error_body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+
+    fn config(body_template: &str) -> HttpSinkConfig {
+        HttpSinkConfig {
+            url: "http://127.0.0.1:1/ingest".to_string(),
+            method: "POST".to_string(),
+            body_template: body_template.to_string(),
+            headers: HashMap::new(),
+            auth: None,
+            timeout_secs: 5,
+        }
+    }
+
+    #[test]
+    fn test_render_body_template() {
+        let sink = HttpSink::new(&config(
+            r#"{"site": "{api_key}", "data": {"power": {value}, "unit": "{unit}", "at": "{timestamp}"}}"#,
+        ))
+        .unwrap();
+
+        let entry = OutboxEntry {
+            api_key: "poi \"north\"".to_string(),
+            reading: Reading {
+                value: 42.5,
+                unit: "kW".to_string(),
+                timestamp: Utc.with_ymd_and_hms(2024, 1, 1, 10, 5, 0).unwrap(),
+                source_id: "csv:/data/meter.csv".to_string(),
+            },
+        };
+
+        let body: serde_json::Value = serde_json::from_str(&sink.render(&entry)).unwrap();
+        assert_eq!(body["site"], "poi \"north\"");
+        assert_eq!(body["data"]["power"], 42.5);
+        assert_eq!(body["data"]["unit"], "kW");
+        assert_eq!(body["data"]["at"], "2024-01-01T10:05:00Z");
+    }
+
+    #[test]
+    fn test_invalid_body_template_is_rejected() {
+        let result = HttpSink::new(&config(r#"{"value": "{value}"#));
+        assert!(matches!(result, Err(TransportError::InvalidConfig(_))));
+    }
+}
@@ -1,5 +1,93 @@
+pub mod auth;
+pub mod http_sink;
 pub mod retry;
 pub mod supabase;
 
-pub use retry::with_retry;
+pub use auth::HttpAuth;
+pub use http_sink::HttpSink;
+pub use retry::{parse_retry_after, with_retry};
 pub use supabase::SupabaseClient;
+
+use crate::config::{SinkConfig, SinkType};
+use crate::error::TransportError;
+use crate::outbox::OutboxEntry;
+use async_trait::async_trait;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
+
+/// A destination readings are delivered to.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn send(&self, entry: &OutboxEntry) -> Result<(), TransportError>;
+
+    /// Maximum number of readings per `send_batch` call, or `None` when batching is not supported.
+    fn batch_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// Send several readings in one request.
+    ///
+    /// The outer error means the whole request failed. Otherwise there is one
+    /// result per entry, in order.
+    async fn send_batch(
+        &self,
+        entries: &[OutboxEntry],
+    ) -> Result<Vec<Result<(), TransportError>>, TransportError> {
+        let _ = entries;
+        Err(TransportError::InvalidConfig(
+            "Batch sending is not supported by this transport".to_string(),
+        ))
+    }
+}
+
+pub fn create_transport(config: &SinkConfig) -> Result<Box<dyn Transport>, TransportError> {
+    match config.sink_type {
+        SinkType::Supabase => {
+            let supabase_config = config.supabase.as_ref().ok_or_else(|| {
+                TransportError::InvalidConfig("Missing Supabase configuration".to_string())
+            })?;
+            Ok(Box::new(SupabaseClient::new(supabase_config)?))
+        }
+        SinkType::Http => {
+            let http_config = config.http.as_ref().ok_or_else(|| {
+                TransportError::InvalidConfig("Missing HTTP sink configuration".to_string())
+            })?;
+            Ok(Box::new(HttpSink::new(http_config)?))
+        }
+    }
+}
+
+pub(crate) fn map_request_error(e: reqwest::Error) -> TransportError {
+    if e.is_timeout() {
+        TransportError::Timeout
+    } else if e.is_connect() {
+        TransportError::Network(format!("Connection failed: {}", e))
+    } else {
+        TransportError::Network(e.to_string())
+    }
+}
+
+pub(crate) fn retry_after_header(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after)
+}
+
+/// Map a failed response to an error. `retry_after` is the parsed `Retry-After` header, if any.
+pub(crate) fn error_for_status(
+    status: StatusCode,
+    retry_after: Option<u64>,
+    error_body: String,
+) -> TransportError {
+    match status.as_u16() {
+        401 | 403 => TransportError::AuthFailed(error_body),
+        429 => TransportError::RateLimited(retry_after),
+        500..=599 => TransportError::ServerError {
+            status: status.as_u16(),
+            message: error_body,
+        },
+        _ => TransportError::InvalidResponse(format!("HTTP {}: {}", status, error_body)),
+    }
+}
@@ -1,18 +1,17 @@
 use crate::config::RetrySettings;
-use crate::error::TransportError;
+use crate::error::Retriable;
 use backoff::backoff::Backoff;
 use backoff::ExponentialBackoff;
+use chrono::{DateTime, Utc};
 use std::future::Future;
 use std::time::Duration;
 use tracing::{debug, warn};
 
-pub async fn with_retry<F, Fut>(
-    settings: &RetrySettings,
-    operation: F,
-) -> Result<(), TransportError>
+pub async fn with_retry<T, E, F, Fut>(settings: &RetrySettings, operation: F) -> Result<T, E>
 where
+    E: Retriable,
     F: Fn() -> Fut,
-    Fut: Future<Output = Result<(), TransportError>>,
+    Fut: Future<Output = Result<T, E>>,
 {
     let mut backoff = ExponentialBackoff {
         initial_interval: Duration::from_millis(settings.initial_delay_ms),
@@ -28,7 +27,7 @@ where
         attempt += 1;
 
         match operation().await {
-            Ok(()) => return Ok(()),
+            Ok(value) => return Ok(value),
             Err(e) => {
                 if attempt >= settings.max_attempts {
                     warn!(
@@ -46,7 +45,7 @@ where
                 }
 
                 // Get the next backoff duration
-                let wait_duration = match backoff.next_backoff() {
+                let mut wait_duration = match backoff.next_backoff() {
                     Some(duration) => duration,
                     None => {
                         warn!("Backoff exhausted");
@@ -54,6 +53,11 @@ where
                     }
                 };
 
+                // Never retry sooner than the server asked us to
+                if let Some(retry_after) = e.retry_after() {
+                    wait_duration = wait_duration.max(clamp_retry_after(settings, retry_after));
+                }
+
                 debug!(
                     attempt = attempt,
                     max_attempts = settings.max_attempts,
@@ -68,16 +72,48 @@ where
     }
 }
 
+/// Cap a server-requested delay at `max_delay_ms` so a huge `Retry-After`
+/// can't stall the caller for hours.
+fn clamp_retry_after(settings: &RetrySettings, retry_after: Duration) -> Duration {
+    let max_delay = Duration::from_millis(settings.max_delay_ms);
+    if retry_after > max_delay {
+        warn!(
+            retry_after_secs = retry_after.as_secs(),
+            max_delay_ms = settings.max_delay_ms,
+            "Retry-After exceeds max_delay_ms, waiting max_delay_ms instead"
+        );
+        return max_delay;
+    }
+    retry_after
+}
+
+/// Parse a `Retry-After` header value, either delay-seconds or an HTTP-date.
+///
+/// Returns the number of seconds to wait; dates in the past give 0.
+pub fn parse_retry_after(value: &str) -> Option<u64> {
+    let value = value.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(secs);
+    }
+
+    // IMF-fixdate ("Wed, 21 Oct 2015 07:28:00 GMT") is valid RFC 2822
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&Utc) - Utc::now();
+    Some(wait.num_seconds().max(0) as u64)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::TransportError;
     use std::sync::atomic::{AtomicU32, Ordering};
     use std::sync::Arc;
 
     #[tokio::test]
     async fn test_retry_success_on_first_attempt() {
         let settings = RetrySettings::default();
-        let result = with_retry(&settings, || async { Ok(()) }).await;
+        let result: Result<(), TransportError> = with_retry(&settings, || async { Ok(()) }).await;
         assert!(result.is_ok());
     }
 
@@ -119,7 +155,7 @@ mod tests {
             multiplier: 2.0,
         };
 
-        let result = with_retry(&settings, || async {
+        let result: Result<(), _> = with_retry(&settings, || async {
             Err(TransportError::Network("persistent error".to_string()))
         })
         .await;
@@ -127,6 +163,80 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("120"), Some(120));
+        assert_eq!(parse_retry_after(" 0 "), Some(0));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(0));
+
+        let in_a_minute = (Utc::now() + chrono::Duration::seconds(61)).to_rfc2822();
+        let secs = parse_retry_after(&in_a_minute).unwrap();
+        assert!((59..=61).contains(&secs), "got {}", secs);
+
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(parse_retry_after("-5"), None);
+    }
+
+    #[tokio::test]
+    async fn test_retry_waits_for_retry_after() {
+        let settings = RetrySettings {
+            max_attempts: 3,
+            initial_delay_ms: 10,
+            max_delay_ms: 2000,
+            multiplier: 2.0,
+        };
+
+        let attempts = Arc::new(AtomicU32::new(0));
+        let attempts_clone = attempts.clone();
+        let started = std::time::Instant::now();
+
+        let result = with_retry(&settings, || {
+            let attempts = attempts_clone.clone();
+            async move {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    Err(TransportError::RateLimited(Some(1)))
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_retry_after_is_capped_at_max_delay() {
+        let settings = RetrySettings {
+            max_attempts: 3,
+            initial_delay_ms: 10,
+            max_delay_ms: 100,
+            multiplier: 2.0,
+        };
+
+        let attempts = Arc::new(AtomicU32::new(0));
+        let attempts_clone = attempts.clone();
+        let started = std::time::Instant::now();
+
+        let result = with_retry(&settings, || {
+            let attempts = attempts_clone.clone();
+            async move {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    Err(TransportError::RateLimited(Some(86400)))
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
     #[tokio::test]
     async fn test_no_retry_on_non_retriable_error() {
         let settings = RetrySettings {
@@ -139,7 +249,7 @@ mod tests {
         let attempts = Arc::new(AtomicU32::new(0));
         let attempts_clone = attempts.clone();
 
-        let result = with_retry(&settings, || {
+        let result: Result<(), _> = with_retry(&settings, || {
             let attempts = attempts_clone.clone();
             async move {
                 attempts.fetch_add(1, Ordering::SeqCst);
@@ -1,7 +1,12 @@
-use crate::config::SupabaseSettings;
+use super::{error_for_status, map_request_error, retry_after_header, Transport};
+use crate::config::{BatchMode, SupabaseSettings};
 use crate::error::TransportError;
+use crate::outbox::OutboxEntry;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
-use serde::Serialize;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 pub struct SupabaseClient {
@@ -9,6 +14,10 @@ pub struct SupabaseClient {
     base_url: String,
     anon_key: String,
     rpc_endpoint: String,
+    send_timestamp: bool,
+    batch_endpoint: Option<String>,
+    batch_mode: BatchMode,
+    batch_size: usize,
 }
 
 #[derive(Debug, Serialize)]
@@ -16,6 +25,32 @@ struct InsertLiveDataRequest {
     p_api_key: String,
     p_value: f64,
     p_unit: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    p_timestamp: Option<DateTime<Utc>>,
+}
+
+/// One reading in a batch, used both as RPC array item and as table row.
+#[derive(Debug, Serialize)]
+struct BatchItem<'a> {
+    api_key: &'a str,
+    value: f64,
+    unit: &'a str,
+    timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+struct InsertLiveDataBatchRequest<'a> {
+    p_readings: Vec<BatchItem<'a>>,
+}
+
+/// Per-reading outcome a bulk RPC function may return, in request order.
+#[derive(Debug, Deserialize)]
+struct BatchItemResult {
+    ok: bool,
+    #[serde(default)]
+    status: Option<u16>,
+    #[serde(default)]
+    error: Option<String>,
 }
 
 impl SupabaseClient {
@@ -30,14 +65,24 @@ impl SupabaseClient {
             base_url: settings.url.clone(),
             anon_key: settings.anon_key.clone(),
             rpc_endpoint: settings.rpc_endpoint.clone(),
+            send_timestamp: settings.send_timestamp,
+            batch_endpoint: settings.batch_endpoint.clone(),
+            batch_mode: settings.batch_mode,
+            batch_size: settings.batch_size,
         })
     }
 
+    /// Maximum number of readings per `insert_live_data_batch` call, or `None` when batching is off.
+    pub fn batch_size(&self) -> Option<usize> {
+        self.batch_endpoint.as_ref().map(|_| self.batch_size)
+    }
+
     pub async fn insert_live_data(
         &self,
         api_key: &str,
         value: f64,
         unit: &str,
+        timestamp: DateTime<Utc>,
     ) -> Result<(), TransportError> {
         let url = format!("{}{}", self.base_url, self.rpc_endpoint);
 
@@ -45,32 +90,17 @@ impl SupabaseClient {
             p_api_key: api_key.to_string(),
             p_value: value,
             p_unit: unit.to_string(),
+            p_timestamp: self.send_timestamp.then_some(timestamp),
         };
 
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            "apikey",
-            HeaderValue::from_str(&self.anon_key)
-                .map_err(|e| TransportError::Network(format!("Invalid API key header: {}", e)))?,
-        );
-        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-
         let response = self
             .client
             .post(&url)
-            .headers(headers)
+            .headers(self.headers()?)
             .json(&body)
             .send()
             .await
-            .map_err(|e| {
-                if e.is_timeout() {
-                    TransportError::Timeout
-                } else if e.is_connect() {
-                    TransportError::Network(format!("Connection failed: {}", e))
-                } else {
-                    TransportError::Network(e.to_string())
-                }
-            })?;
+            .map_err(map_request_error)?;
 
         let status = response.status();
 
@@ -79,22 +109,242 @@ impl SupabaseClient {
         }
 
         // Handle error responses
+        let retry_after = retry_after_header(&response);
         let error_body = response.text().await.unwrap_or_default();
+        Err(error_for_status(status, retry_after, error_body))
+    }
+
+    /// Send several readings in one request.
+    ///
+    /// The outer error means the whole request failed. Otherwise there is one
+    /// result per entry, in order; bulk RPC functions that don't report
+    /// per-reading results are assumed to have accepted everything.
+    pub async fn insert_live_data_batch(
+        &self,
+        entries: &[OutboxEntry],
+    ) -> Result<Vec<Result<(), TransportError>>, TransportError> {
+        let endpoint = self.batch_endpoint.as_ref().ok_or_else(|| {
+            TransportError::InvalidResponse("No batch endpoint configured".to_string())
+        })?;
+        let url = format!("{}{}", self.base_url, endpoint);
+
+        let items: Vec<BatchItem> = entries
+            .iter()
+            .map(|entry| BatchItem {
+                api_key: &entry.api_key,
+                value: entry.reading.value,
+                unit: &entry.reading.unit,
+                timestamp: entry.reading.timestamp,
+            })
+            .collect();
+
+        let request = self.client.post(&url).headers(self.headers()?);
+        let request = match self.batch_mode {
+            BatchMode::Rpc => request.json(&InsertLiveDataBatchRequest { p_readings: items }),
+            BatchMode::Table => request.header("Prefer", "return=minimal").json(&items),
+        };
+
+        let response = request.send().await.map_err(map_request_error)?;
+        let status = response.status();
+        let retry_after = retry_after_header(&response);
+        let body = response.text().await.unwrap_or_default();
+
+        if !status.is_success() {
+            return Err(error_for_status(status, retry_after, body));
+        }
+
+        match self.batch_mode {
+            BatchMode::Rpc => parse_batch_response(&body, entries.len()),
+            BatchMode::Table => Ok(entries.iter().map(|_| Ok(())).collect()),
+        }
+    }
+
+    fn headers(&self) -> Result<HeaderMap, TransportError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "apikey",
+            HeaderValue::from_str(&self.anon_key)
+                .map_err(|e| TransportError::Network(format!("Invalid API key header: {}", e)))?,
+        );
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(headers)
+    }
+}
+
+#[async_trait]
+impl Transport for SupabaseClient {
+    async fn send(&self, entry: &OutboxEntry) -> Result<(), TransportError> {
+        self.insert_live_data(
+            &entry.api_key,
+            entry.reading.value,
+            &entry.reading.unit,
+            entry.reading.timestamp,
+        )
+        .await
+    }
+
+    fn batch_size(&self) -> Option<usize> {
+        SupabaseClient::batch_size(self)
+    }
+
+    async fn send_batch(
+        &self,
+        entries: &[OutboxEntry],
+    ) -> Result<Vec<Result<(), TransportError>>, TransportError> {
+        self.insert_live_data_batch(entries).await
+    }
+}
+
+/// Per-reading results of an RPC batch call.
+///
+/// A body that is not an array (void function, row count, ...) means the batch
+/// was accepted as a whole. An array must hold one result per reading, otherwise
+/// nothing can be acknowledged.
+fn parse_batch_response(
+    body: &str,
+    expected: usize,
+) -> Result<Vec<Result<(), TransportError>>, TransportError> {
+    let accepted = || Ok((0..expected).map(|_| Ok(())).collect());
+    let items = match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(serde_json::Value::Array(items)) => items,
+        _ => return accepted(),
+    };
+    if items.len() != expected {
+        return Err(TransportError::InvalidResponse(format!(
+            "Batch response has {} results for {} readings",
+            items.len(),
+            expected
+        )));
+    }
 
-        match status.as_u16() {
-            401 | 403 => Err(TransportError::AuthFailed(error_body)),
-            429 => {
-                // Try to extract retry-after header
-                Err(TransportError::RateLimited(60))
+    items
+        .into_iter()
+        .map(|item| {
+            let result = serde_json::from_value::<BatchItemResult>(item).map_err(|e| {
+                TransportError::InvalidResponse(format!("Invalid batch result: {}", e))
+            })?;
+            if result.ok {
+                return Ok(Ok(()));
             }
-            500..=599 => Err(TransportError::ServerError {
-                status: status.as_u16(),
-                message: error_body,
-            }),
-            _ => Err(TransportError::InvalidResponse(format!(
-                "HTTP {}: {}",
-                status, error_body
-            ))),
+            let message = result.error.unwrap_or_default();
+            Ok(
+                match result.status.and_then(|s| StatusCode::from_u16(s).ok()) {
+                    Some(status) => Err(error_for_status(status, None, message)),
+                    None => Err(TransportError::InvalidResponse(message)),
+                },
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RetrySettings;
+    use crate::error::Retriable;
+    use crate::test_server::MockServer;
+    use crate::transport::with_retry;
+    use chrono::TimeZone;
+
+    fn settings(url: String) -> SupabaseSettings {
+        toml::from_str(&format!("url = \"{}\"\nanon_key = \"anon\"", url)).unwrap()
+    }
+
+    #[test]
+    fn test_request_includes_timestamp_only_when_set() {
+        let timestamp = Utc.with_ymd_and_hms(2024, 1, 1, 10, 5, 0).unwrap();
+        let mut body = InsertLiveDataRequest {
+            p_api_key: "key".to_string(),
+            p_value: 42.5,
+            p_unit: "kW".to_string(),
+            p_timestamp: Some(timestamp),
+        };
+
+        let json = serde_json::to_value(&body).unwrap();
+        assert_eq!(json["p_timestamp"], "2024-01-01T10:05:00Z");
+
+        body.p_timestamp = None;
+        let json = serde_json::to_value(&body).unwrap();
+        assert!(json.get("p_timestamp").is_none());
+    }
+
+    #[test]
+    fn test_parse_batch_response_maps_item_errors() {
+        let body = r#"[
+            {"ok": true},
+            {"ok": false, "status": 400, "error": "unknown POI"},
+            {"ok": false, "status": 503, "error": "busy"},
+            {"ok": false}
+        ]"#;
+
+        let results = parse_batch_response(body, 4).unwrap();
+        assert!(results[0].is_ok());
+        assert!(matches!(
+            results[1],
+            Err(TransportError::InvalidResponse(_))
+        ));
+        assert!(results[2].as_ref().unwrap_err().is_retriable());
+        assert!(!results[3].as_ref().unwrap_err().is_retriable());
+    }
+
+    #[test]
+    fn test_parse_batch_response_without_item_results() {
+        for body in ["", "null", "2", "{\"inserted\": 2}"] {
+            let results = parse_batch_response(body, 2).unwrap();
+            assert_eq!(results.len(), 2);
+            assert!(results.iter().all(|r| r.is_ok()));
         }
     }
+
+    #[test]
+    fn test_parse_batch_response_rejects_unmatched_results() {
+        for body in [
+            "[]",
+            r#"[{"ok": true}]"#,
+            r#"[{"ok": true}, {"status": 201}]"#,
+        ] {
+            assert!(matches!(
+                parse_batch_response(body, 2),
+                Err(TransportError::InvalidResponse(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_honors_retry_after() {
+        let rate_limited = "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let server = MockServer::with_responses(vec![
+            rate_limited,
+            rate_limited,
+            "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n",
+        ])
+        .await;
+        let client = SupabaseClient::new(&settings(server.url.clone())).unwrap();
+        let retry = RetrySettings {
+            max_attempts: 3,
+            initial_delay_ms: 10,
+            max_delay_ms: 2000,
+            multiplier: 2.0,
+        };
+
+        let err = client
+            .insert_live_data("key", 1.0, "kW", Utc::now())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, TransportError::RateLimited(Some(1))));
+
+        let started = std::time::Instant::now();
+        let result = with_retry(&retry, || {
+            client.insert_live_data("key", 1.0, "kW", Utc::now())
+        })
+        .await;
+
+        assert!(result.is_ok());
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        let body: serde_json::Value = serde_json::from_str(&requests[2].body).unwrap();
+        assert_eq!(body["p_api_key"], "key");
+        assert_eq!(body["p_value"], 1.0);
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
 }
@@ -16,12 +16,16 @@ struct ReleaseManifest {
     assets: Assets,
 }
 
+// Only the asset for the current platform is deserialized
 #[derive(Debug, Deserialize)]
 struct Assets {
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
     #[serde(rename = "macos-aarch64")]
     macos_aarch64: Option<Asset>,
+    #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
     #[serde(rename = "macos-x86_64")]
     macos_x86_64: Option<Asset>,
+    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
     #[serde(rename = "windows-x86_64")]
     windows_x86_64: Option<Asset>,
 }
@@ -72,7 +76,7 @@ pub async fn check_and_update(force: bool) -> Result<bool, UpdateError> {
     let asset = get_platform_asset(&manifest.assets)?;
 
     // Download and install
-    download_and_install(&asset).await?;
+    download_and_install(asset).await?;
 
     Ok(true)
 }
@@ -99,6 +103,11 @@ async fn fetch_manifest(url: &str) -> Result<ReleaseManifest, UpdateError> {
         .map_err(|e| UpdateError::CheckFailed(e.to_string()))
 }
 
+#[cfg(any(
+    all(target_os = "macos", target_arch = "aarch64"),
+    all(target_os = "macos", target_arch = "x86_64"),
+    all(target_os = "windows", target_arch = "x86_64")
+))]
 fn get_platform_asset(assets: &Assets) -> Result<&Asset, UpdateError> {
     #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
     {
@@ -123,17 +132,17 @@ fn get_platform_asset(assets: &Assets) -> Result<&Asset, UpdateError> {
             .as_ref()
             .ok_or_else(|| UpdateError::CheckFailed("No Windows x86_64 asset found".to_string()))
     }
+}
 
-    #[cfg(not(any(
-        all(target_os = "macos", target_arch = "aarch64"),
-        all(target_os = "macos", target_arch = "x86_64"),
-        all(target_os = "windows", target_arch = "x86_64")
-    )))]
-    {
-        Err(UpdateError::CheckFailed(
-            "Unsupported platform for auto-update".to_string(),
-        ))
-    }
+#[cfg(not(any(
+    all(target_os = "macos", target_arch = "aarch64"),
+    all(target_os = "macos", target_arch = "x86_64"),
+    all(target_os = "windows", target_arch = "x86_64")
+)))]
+fn get_platform_asset(_assets: &Assets) -> Result<&Asset, UpdateError> {
+    Err(UpdateError::CheckFailed(
+        "Unsupported platform for auto-update".to_string(),
+    ))
 }
 
 async fn download_and_install(asset: &Asset) -> Result<(), UpdateError> {